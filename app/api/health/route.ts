@@ -0,0 +1,7 @@
+// The desktop app polls this until it answers "ok" before showing the UI
+// (OLLY_HEALTH_PATH), so it only does once API routes are being served.
+export const dynamic = 'force-dynamic';
+
+export async function GET() {
+    return new Response('ok', { headers: { 'Content-Type': 'text/plain' } });
+}
@@ -8,8 +8,9 @@ export interface ImageGeneratorSettings {
     geminiApiKey?: string;
 }
 
-// Store settings in a file in the db directory (persistent across restarts)
-const SETTINGS_FILE = path.join(process.cwd(), 'db', 'image-settings.json');
+// Store settings in a file in the db directory (persistent across restarts),
+// or the desktop app's data directory when it provides one
+const SETTINGS_FILE = path.join(process.env.OLLY_DATA_DIR || path.join(process.cwd(), 'db'), 'image-settings.json');
 
 const defaultSettings: ImageGeneratorSettings = {
     provider: 'off',
@@ -0,0 +1,12 @@
+import { NextResponse } from 'next/server';
+
+// Lets the desktop app recognise a server left running by a previous run
+// (see src-tauri/src/leftover.rs). It only reads the header.
+export const dynamic = 'force-dynamic';
+
+export async function GET() {
+    return NextResponse.json(
+        { app: 'olly-molly', pid: process.pid },
+        { headers: { 'X-Olly-Server': String(process.pid) } }
+    );
+}
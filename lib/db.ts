@@ -9,8 +9,11 @@ const isBuildPhase = process.env.NEXT_PHASE === 'phase-production-build' ||
   process.argv.includes('build') ||
   process.env.npm_lifecycle_event === 'build';
 
-// Database file path
-const DB_PATH = path.join(process.cwd(), 'db', 'dev.sqlite');
+// Database file path. The desktop app passes OLLY_DATA_DIR because its
+// working directory (the bundled server) may be read-only.
+const DB_PATH = process.env.OLLY_DATA_DIR
+  ? path.join(process.env.OLLY_DATA_DIR, 'dev.sqlite')
+  : path.join(process.cwd(), 'db', 'dev.sqlite');
 
 // Lazy database connection - only initialize when actually needed
 let _db: DatabaseType | null = null;
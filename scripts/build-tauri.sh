@@ -22,6 +22,8 @@ cp -R .next/static src-tauri/server/.next/
 cp -R public src-tauri/server/
 mkdir -p src-tauri/server/db
 cp db/*.sql src-tauri/server/db/
+# Lets the server listen over HTTPS when the app runs with OLLY_HTTPS=1
+cp scripts/https-preload.cjs src-tauri/server/
 
 # Remove unnecessary files
 rm -rf src-tauri/server/src-tauri 2>/dev/null || true
@@ -34,6 +36,12 @@ rm -f src-tauri/server/*.mjs 2>/dev/null || true
 rm -f src-tauri/server/*.ts 2>/dev/null || true
 rm -f src-tauri/server/package-lock.json 2>/dev/null || true
 
+# Bundle a Node.js runtime so the app works without a system install: the
+# official build prepare-node-sidecar.sh downloads, since the node on PATH
+# (e.g. Homebrew's) links against libraries users won't have. It honours
+# NODE_VERSION and NODE_BIN the same way here.
+NODE_DEST=src-tauri/server/node ./scripts/prepare-node-sidecar.sh
+
 echo "Server directory size: $(du -sh src-tauri/server | cut -f1)"
 
 # Step 3: Build Tauri app
@@ -75,5 +83,6 @@ echo ""
 echo "App size:   $(du -sh "$APP_PATH" | cut -f1)"
 echo "DMG size:   $(du -sh "$DMG_PATH" | cut -f1)"
 echo ""
-echo "NOTE: This app requires Node.js to be installed on the target machine."
-echo "      Install via: https://nodejs.org or 'brew install node'"
+echo "NOTE: Node.js $(src-tauri/server/node --version) is bundled in the app resources."
+echo "      The app falls back to a system install if the bundled copy is"
+echo "      missing, fails to run or is older than it needs."
@@ -0,0 +1,19 @@
+// Loaded with `node --require` when the desktop app serves over HTTPS
+// (OLLY_HTTPS=1). The Next.js standalone server always calls
+// http.createServer, so hand it an HTTPS server using the certificate the
+// app generated instead.
+const fs = require("fs");
+const http = require("http");
+const https = require("https");
+
+const tls = {
+  cert: fs.readFileSync(process.env.OLLY_TLS_CERT),
+  key: fs.readFileSync(process.env.OLLY_TLS_KEY),
+};
+
+http.createServer = (options, listener) => {
+  if (typeof options === "function") {
+    return https.createServer(tls, options);
+  }
+  return https.createServer({ ...options, ...tls }, listener);
+};
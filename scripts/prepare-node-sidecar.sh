@@ -0,0 +1,82 @@
+#!/bin/bash
+set -e
+
+# Put a Node.js binary in src-tauri/binaries so it can be bundled as a
+# Tauri sidecar. Tauri expects the file name to carry the target triple.
+#
+# By default this downloads the official build of NODE_VERSION from
+# nodejs.org, which only links against system libraries and so runs on any
+# machine the app does. NODE_BIN copies a binary of your own instead (not
+# NODE_PATH, which is Node's module search path); it has to be just as
+# self-contained. A Homebrew or distro node links against libraries under
+# /opt/homebrew or /usr/lib that users won't have.
+#
+# NODE_DEST puts the binary somewhere else; build-tauri.sh uses it to bundle
+# the same download into the server directory.
+TARGET_TRIPLE=${TARGET_TRIPLE:-$(rustc -vV | sed -n 's/^host: //p')}
+NODE_VERSION=${NODE_VERSION:-20.18.1}
+
+EXT=""
+case "$TARGET_TRIPLE" in
+    *windows*) EXT=".exe" ;;
+esac
+DEST=${NODE_DEST:-src-tauri/binaries/node-$TARGET_TRIPLE$EXT}
+mkdir -p "$(dirname "$DEST")"
+
+sha256() {
+    if command -v sha256sum >/dev/null; then
+        sha256sum "$1" | cut -d' ' -f1
+    else
+        shasum -a 256 "$1" | cut -d' ' -f1
+    fi
+}
+
+if [ -n "$NODE_BIN" ]; then
+    echo "Copying $NODE_BIN, which must not depend on libraries outside the system"
+    cp "$NODE_BIN" "$DEST"
+else
+    case "$TARGET_TRIPLE" in
+        aarch64-apple-darwin) PLATFORM=darwin-arm64 ;;
+        x86_64-apple-darwin) PLATFORM=darwin-x64 ;;
+        aarch64-unknown-linux-gnu) PLATFORM=linux-arm64 ;;
+        x86_64-unknown-linux-gnu) PLATFORM=linux-x64 ;;
+        aarch64-pc-windows-msvc) PLATFORM=win-arm64 ;;
+        x86_64-pc-windows-msvc) PLATFORM=win-x64 ;;
+        *)
+            echo "No official Node.js build for $TARGET_TRIPLE; set NODE_BIN to a self-contained node" >&2
+            exit 1
+            ;;
+    esac
+
+    BASE_URL="https://nodejs.org/dist/v$NODE_VERSION"
+    # Windows has a standalone node.exe; elsewhere node comes in a tarball
+    case "$PLATFORM" in
+        win-*) ARCHIVE="$PLATFORM/node.exe" ;;
+        *) ARCHIVE="node-v$NODE_VERSION-$PLATFORM.tar.gz" ;;
+    esac
+
+    WORK_DIR=$(mktemp -d)
+    trap 'rm -rf "$WORK_DIR"' EXIT
+    echo "Downloading Node.js $NODE_VERSION for $PLATFORM..."
+    curl -fsSL -o "$WORK_DIR/download" "$BASE_URL/$ARCHIVE"
+    curl -fsSL -o "$WORK_DIR/SHASUMS256.txt" "$BASE_URL/SHASUMS256.txt"
+
+    EXPECTED=$(awk -v name="$ARCHIVE" '$2 == name { print $1 }' "$WORK_DIR/SHASUMS256.txt")
+    if [ -z "$EXPECTED" ] || [ "$(sha256 "$WORK_DIR/download")" != "$EXPECTED" ]; then
+        echo "Checksum mismatch for $ARCHIVE" >&2
+        exit 1
+    fi
+
+    case "$PLATFORM" in
+        win-*) cp "$WORK_DIR/download" "$DEST" ;;
+        *) tar -xzf "$WORK_DIR/download" -O "node-v$NODE_VERSION-$PLATFORM/bin/node" > "$DEST" ;;
+    esac
+fi
+chmod +x "$DEST"
+
+if [ -n "$NODE_DEST" ]; then
+    echo "Node.js $("$DEST" --version) prepared at $DEST for $TARGET_TRIPLE"
+else
+    echo "Node.js $("$DEST" --version) prepared as sidecar for $TARGET_TRIPLE"
+    echo "Now run: npm run tauri:build:sidecar"
+fi
@@ -15,6 +15,8 @@ cp -r .next/static src-tauri/server/.next/
 cp -r public src-tauri/server/
 mkdir -p src-tauri/server/db
 cp db/*.sql src-tauri/server/db/
+# Lets the server listen over HTTPS when the app runs with OLLY_HTTPS=1
+cp scripts/https-preload.cjs src-tauri/server/
 
 # Remove unnecessary files from server bundle
 rm -rf src-tauri/server/src-tauri
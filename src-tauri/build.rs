@@ -1,3 +1,26 @@
 fn main() {
-  tauri_build::build()
+  // Listing the commands makes Tauri check them against the capabilities,
+  // so a page only reaches the commands its capability allows
+  tauri_build::try_build(tauri_build::Attributes::new().app_manifest(
+    tauri_build::AppManifest::new().commands(&[
+      "stop_server",
+      "restart_server",
+      "server_port",
+      "server_pid",
+      "get_server_stats",
+      "watch_server_stats",
+      "get_server_url",
+      "server_health",
+      "server_status",
+      "recent_server_logs",
+      "get_recent_logs",
+      "server_log_path",
+      "reveal_server_dir",
+      "open_logs_dir",
+      "reveal_server_logs",
+      "start_named_server",
+      "stop_named_server",
+    ]),
+  ))
+  .expect("failed to run tauri-build");
 }
@@ -0,0 +1,43 @@
+use std::ffi::OsStr;
+use std::process::Command;
+
+/// Keeps a console program started from the GUI app from opening a console
+/// window of its own, which flashes up on every call.
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// `Command::new` for every process the app starts, so that none of them
+/// flashes a console window on Windows. Piped stdio is unaffected, and
+/// `taskkill` still reaches the process tree.
+pub(crate) fn command(program: impl AsRef<OsStr>) -> Command {
+    #[cfg_attr(not(windows), allow(unused_mut))]
+    let mut command = Command::new(program);
+    #[cfg(windows)]
+    std::os::windows::process::CommandExt::creation_flags(&mut command, CREATE_NO_WINDOW);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[cfg(windows)]
+    #[test]
+    fn windowless_command_output_is_captured() {
+        let output = command("cmd").args(["/C", "echo out& echo err 1>&2"]).output().unwrap();
+        
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "out");
+        assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), "err");
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn command_output_is_captured() {
+        let output = command("sh").args(["-c", "echo out; echo err >&2"]).output().unwrap();
+        
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "out\n");
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "err\n");
+    }
+}
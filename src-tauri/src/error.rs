@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Why the bundled server couldn't be started or restarted. `Display` gives
+/// the message shown to the user; commands serialize it as
+/// `{ kind, message, needs_node_install }` for the frontend.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ServerError {
+    #[error(
+        "The server files could not be found in the application bundle (looked in {}). Please reinstall Olly Molly.",
+        display_paths(searched)
+    )]
+    ServerDirNotFound { searched: Vec<PathBuf> },
+    #[error("The server files are incomplete: {} is missing. Please reinstall Olly Molly.", .0.display())]
+    ServerJsMissing(PathBuf),
+    #[error(
+        "Olly Molly requires Node.js {} or newer, but it was not found. Install it from nodejs.org and restart Olly Molly.",
+        crate::node::format_version(*required)
+    )]
+    NodeNotFound { required: (u32, u32, u32) },
+    #[error(
+        "Olly Molly requires Node.js {} or newer, found {found} at {}. Please upgrade from nodejs.org.",
+        crate::node::format_version(*required),
+        path.display()
+    )]
+    NodeTooOld { found: String, path: PathBuf, required: (u32, u32, u32) },
+    /// The binary exists but `node --version` failed or printed garbage.
+    #[error("{0}. Please reinstall Node.js from nodejs.org.")]
+    NodeUnusable(String),
+    #[error("Failed to start the server: {0}")]
+    SpawnFailed(#[source] std::io::Error),
+    #[error(
+        "The server did not respond on port {port} within {timeout:?}: {}",
+        stderr_tail.lines().last().unwrap_or("no output on stderr")
+    )]
+    StartupTimeout { port: u16, timeout: Duration, stderr_tail: String },
+    #[error(
+        "Port {port} is already in use{}. Quit the other program or set OLLY_PORT to a free port, then restart Olly Molly.",
+        holder.as_ref().map(|holder| format!(" by {}", holder)).unwrap_or_default()
+    )]
+    PortInUse { port: u16, holder: Option<String> },
+    #[error(
+        "The server stopped during startup ({status}): {}",
+        stderr_tail.lines().last().unwrap_or("no output on stderr")
+    )]
+    ServerExited { status: String, stderr_tail: String },
+    /// Every startup attempt timed out or died; one entry per attempt.
+    #[error("The server failed to start after {} attempts. {}", attempts.len(), display_attempts(attempts))]
+    StartupFailed { attempts: Vec<ServerError> },
+    #[error("A server restart is already in progress")]
+    RestartInProgress,
+    #[error("A server named {0:?} is already running")]
+    ServerNameInUse(String),
+    #[error("Invalid server name {0:?}: use letters, digits, '-' and '_'")]
+    InvalidServerName(String),
+    #[error("Failed to open the app window: {0}")]
+    Window(String),
+    /// The app started quitting before the server came up.
+    #[error("Server startup was cancelled because Olly Molly is quitting")]
+    Cancelled,
+    /// The thread a command did its work on panicked.
+    #[error("The server task failed: {0}")]
+    Task(String),
+}
+
+impl ServerError {
+    /// Whether installing or upgrading Node.js fixes this failure.
+    pub(crate) fn needs_node_install(&self) -> bool {
+        matches!(
+            self,
+            ServerError::NodeNotFound { .. } | ServerError::NodeTooOld { .. } | ServerError::NodeUnusable(_)
+        )
+    }
+    
+    /// Whether the app bundle itself is broken and reinstalling fixes it.
+    pub(crate) fn needs_reinstall(&self) -> bool {
+        matches!(self, ServerError::ServerDirNotFound { .. } | ServerError::ServerJsMissing(_))
+    }
+    
+    /// Stable identifier the frontend can branch on.
+    fn kind(&self) -> &'static str {
+        match self {
+            ServerError::ServerDirNotFound { .. } => "server_dir_not_found",
+            ServerError::ServerJsMissing(_) => "server_js_missing",
+            ServerError::NodeNotFound { .. } => "node_not_found",
+            ServerError::NodeTooOld { .. } => "node_too_old",
+            ServerError::NodeUnusable(_) => "node_unusable",
+            ServerError::SpawnFailed(_) => "spawn_failed",
+            ServerError::StartupTimeout { .. } => "startup_timeout",
+            ServerError::PortInUse { .. } => "port_in_use",
+            ServerError::ServerExited { .. } => "server_exited",
+            ServerError::StartupFailed { .. } => "startup_failed",
+            ServerError::RestartInProgress => "restart_in_progress",
+            ServerError::ServerNameInUse(_) => "server_name_in_use",
+            ServerError::InvalidServerName(_) => "invalid_server_name",
+            ServerError::Window(_) => "window",
+            ServerError::Cancelled => "cancelled",
+            ServerError::Task(_) => "task",
+        }
+    }
+}
+
+impl serde::Serialize for ServerError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        
+        let mut error = serializer.serialize_struct("ServerError", 3)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.serialize_field("needs_node_install", &self.needs_node_install())?;
+        error.end()
+    }
+}
+
+fn display_paths(paths: &[PathBuf]) -> String {
+    let paths: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+    paths.join(", ")
+}
+
+fn display_attempts(attempts: &[ServerError]) -> String {
+    let attempts: Vec<String> = attempts
+        .iter()
+        .enumerate()
+        .map(|(number, error)| format!("Attempt {}: {}", number + 1, error))
+        .collect();
+    attempts.join(" ")
+}
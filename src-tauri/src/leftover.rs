@@ -0,0 +1,223 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+#[cfg(windows)]
+use crate::command::command;
+
+/// Route the Next.js server answers with its PID in `IDENTITY_HEADER`, so a
+/// server left running by a crashed app can be told apart from some other
+/// program on the port (see app/api/olly-identity).
+pub(crate) const IDENTITY_PATH: &str = "/api/olly-identity";
+const IDENTITY_HEADER: &str = "x-olly-server";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const FORCED_EXIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What to do with a server of ours found on the port at startup.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LeftoverPolicy {
+    /// Use it as is and stop it when the app quits
+    Adopt,
+    /// Stop it and start a fresh one
+    Replace,
+}
+
+impl LeftoverPolicy {
+    /// From `OLLY_LEFTOVER_SERVER` (`adopt` or `replace`). Replacing is the
+    /// default: the leftover may be from an older build, and a fresh server
+    /// picks up the current `server-env.json`.
+    pub(crate) fn from_env() -> LeftoverPolicy {
+        match std::env::var("OLLY_LEFTOVER_SERVER") {
+            Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "adopt" => LeftoverPolicy::Adopt,
+                "replace" => LeftoverPolicy::Replace,
+                _ => {
+                    log::warn!("Ignoring invalid OLLY_LEFTOVER_SERVER={:?}, replacing leftovers", value);
+                    LeftoverPolicy::Replace
+                }
+            },
+            Err(_) => LeftoverPolicy::Replace,
+        }
+    }
+}
+
+/// Asks whatever listens on `addr` for `path` and returns the PID from the
+/// identity header if it is one of our servers. `None` if nothing listens,
+/// it doesn't speak HTTP or it doesn't know the route.
+pub(crate) fn identify_server(addr: &SocketAddr, path: &str) -> Option<u32> {
+    let mut stream = TcpStream::connect_timeout(addr, PROBE_TIMEOUT).ok()?;
+    let _ = stream.set_read_timeout(Some(PROBE_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(PROBE_TIMEOUT));
+    
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, addr);
+    stream.write_all(request.as_bytes()).ok()?;
+    
+    // Only the head is needed; stop there rather than waiting for the body
+    let mut response = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !response.windows(4).any(|window| window == b"\r\n\r\n") && response.len() < 16 * 1024 {
+        match stream.read(&mut chunk) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => response.extend_from_slice(&chunk[..read]),
+        }
+    }
+    parse_identity(&String::from_utf8_lossy(&response))
+}
+
+/// The PID from the identity header of a successful response head, if it
+/// is one that may be signalled.
+fn parse_identity(response: &str) -> Option<u32> {
+    let mut lines = response.lines();
+    let status = lines.next()?.split_whitespace().nth(1)?;
+    if status != "200" {
+        return None;
+    }
+    lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case(IDENTITY_HEADER))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .filter(|&pid| signallable(pid))
+}
+
+/// Rules out the PIDs `kill` and `taskkill` treat specially or that aren't a
+/// server at all: 0 (our own process group), 1 (init, and -1 is every
+/// process we may signal), values that wrap to a negative `pid_t` and the
+/// app itself.
+fn signallable(pid: u32) -> bool {
+    pid > 1 && pid <= i32::MAX as u32 && pid != std::process::id()
+}
+
+/// Stops a process we didn't spawn, politely first and forcibly after
+/// `grace`. Returns whether it is gone.
+pub(crate) fn terminate_pid(pid: u32, grace: Duration) -> bool {
+    if !signallable(pid) {
+        log::error!("Refusing to signal PID {}", pid);
+        return false;
+    }
+    if request_exit(pid, false) && wait_for_exit(pid, grace) {
+        return true;
+    }
+    log::warn!("PID {} did not exit within {:?}, killing it", pid, grace);
+    request_exit(pid, true) && wait_for_exit(pid, FORCED_EXIT_TIMEOUT)
+}
+
+fn wait_for_exit(pid: u32, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while process_alive(pid) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(EXIT_POLL_INTERVAL);
+    }
+    true
+}
+
+#[cfg(unix)]
+fn request_exit(pid: u32, force: bool) -> bool {
+    let signal = if force { libc::SIGKILL } else { libc::SIGTERM };
+    let pid = pid as libc::pid_t;
+    // SAFETY: kill(2) has no memory-safety preconditions. terminate_pid
+    // checked the PID is positive and not ours, and callers checked that
+    // it holds the server's port. Servers lead their own process group,
+    // unless an older build started it.
+    unsafe { libc::kill(-pid, signal) == 0 || libc::kill(pid, signal) == 0 }
+}
+
+#[cfg(windows)]
+fn request_exit(pid: u32, force: bool) -> bool {
+    let mut command = command("taskkill");
+    command.args(["/PID", &pid.to_string(), "/T"]);
+    if force {
+        command.arg("/F");
+    }
+    command.output().map(|output| output.status.success()).unwrap_or(false)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn request_exit(_pid: u32, _force: bool) -> bool {
+    false
+}
+
+/// Whether `pid` is still running. An orphaned server is reaped by init, so
+/// on Unix it stops existing as soon as it exits.
+#[cfg(unix)]
+pub(crate) fn process_alive(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists
+    let alive = unsafe { libc::kill(pid as libc::pid_t, 0) == 0 };
+    // EPERM: it exists but belongs to someone else
+    alive || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+pub(crate) fn process_alive(pid: u32) -> bool {
+    let Ok(output) = command("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+        .output()
+    else {
+        return false;
+    };
+    // "node.exe","4321",... or an INFO line when there is no match
+    String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn process_alive(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    
+    /// Serves `response` to the first connection and returns its address.
+    fn serve_once(response: &'static str) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+            let _ = stream.write_all(response.as_bytes());
+        });
+        addr
+    }
+    
+    #[test]
+    fn identity_header_gives_pid() {
+        let response = "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\nX-Olly-Server: 4321\r\n\r\n{}";
+        assert_eq!(parse_identity(response), Some(4321));
+    }
+    
+    #[test]
+    fn other_servers_are_not_ours() {
+        assert_eq!(parse_identity("HTTP/1.1 200 OK\r\ncontent-type: text/html\r\n\r\nx-olly-server: 1"), None);
+        assert_eq!(parse_identity("HTTP/1.1 404 Not Found\r\nx-olly-server: 4321\r\n\r\n"), None);
+        assert_eq!(parse_identity("SSH-2.0-OpenSSH_9.6\r\n"), None);
+        assert_eq!(parse_identity(""), None);
+    }
+    
+    #[test]
+    fn special_pids_are_rejected() {
+        for pid in ["0", "1", "-1", "4294967295", &std::process::id().to_string()] {
+            let response = format!("HTTP/1.1 200 OK\r\nx-olly-server: {}\r\n\r\n", pid);
+            assert_eq!(parse_identity(&response), None, "{}", pid);
+        }
+        assert!(!terminate_pid(1, Duration::ZERO));
+    }
+    
+    #[test]
+    fn identify_server_reads_header_from_socket() {
+        let addr = serve_once("HTTP/1.1 200 OK\r\nx-olly-server: 99\r\nconnection: close\r\n\r\n{\"pid\":99}");
+        
+        assert_eq!(identify_server(&addr, IDENTITY_PATH), Some(99));
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn own_process_is_alive() {
+        assert!(process_alive(std::process::id()));
+    }
+}
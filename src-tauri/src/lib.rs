@@ -1,156 +1,3693 @@
-use std::process::{Command, Child, Stdio};
-use std::sync::Mutex;
-use std::path::PathBuf;
-use tauri::Manager;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::process::{Command, Child, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use regex::Regex;
+use tauri::menu::{Menu, MenuEvent, MenuItem, HELP_SUBMENU_ID};
+use tauri::{Emitter, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use tauri_plugin_opener::OpenerExt;
+
+mod command;
+mod error;
+mod leftover;
+mod logs;
+mod node;
+mod stats;
+mod tls;
+mod tray;
+
+use command::command;
+use error::ServerError;
+use leftover::{identify_server, process_alive, terminate_pid, LeftoverPolicy, IDENTITY_PATH};
+use logs::{LogBuffer, LogLine, ServerLog};
+use node::{find_node_binary, min_node_version, NodeRuntime};
+use stats::{ProcessStats, StatsSampler};
+use tls::{ensure_self_signed, https_exchange, https_requested, TlsFiles, HTTPS_PRELOAD};
+
+// Also in the package.json scripts, which a test checks. tauri.conf.json's
+// `devUrl` is only what `tauri dev` waits for; the app doesn't read it.
+const DEFAULT_PORT: u16 = 1234;
+const DEFAULT_HOSTNAME: &str = "127.0.0.1";
+const DEFAULT_SERVER_SUBDIR: &str = "server";
+const SERVER_ENV_FILE: &str = "server-env.json";
+const APP_CONFIG_FILE: &str = "config.json";
+const PORT_ATTEMPTS: usize = 3;
+const SPAWN_ATTEMPTS: usize = 4;
+const SPAWN_RETRY_DELAY: Duration = Duration::from_millis(250);
+const SPLASH_WINDOW: &str = "splash";
+const DEFAULT_SERVER_NAME: &str = "default";
+const OPEN_LOGS_MENU_ID: &str = "open_logs";
+const NODE_DOWNLOAD_URL: &str = "https://nodejs.org/en/download";
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_STARTUP_ATTEMPTS: usize = 3;
+const DEFAULT_STARTUP_BUDGET: Duration = Duration::from_secs(120);
+const DEFAULT_READY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const READY_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+const DEV_SERVER_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+// Quitting blocks on the shutdown, so cap it before the app looks hung
+const MAX_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(10);
+const PANIC_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(1);
+const STDERR_TAIL_LINES: usize = 50;
+const STDERR_DRAIN_DELAY: Duration = Duration::from_millis(200);
+const DEFAULT_HEALTH_PATH: &str = "/api/health";
+const MAX_PROBE_RESPONSE_BYTES: u64 = 64 * 1024;
+// Matches `✓ Ready in 843ms` / `✓ Ready in 1.2s`; the groups give the time
+// Next.js itself took to start
+const DEFAULT_READY_PATTERN: &str = r"Ready in (\d+(?:\.\d+)?)\s*(ms|s)\b";
+const CRASH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_HEALTH_INTERVAL: Duration = Duration::from_secs(15);
+const UNHEALTHY_AFTER_FAILURES: u32 = 2;
+const MIN_STATS_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_CRASH_RESTARTS: usize = 5;
+const CRASH_RESTART_WINDOW: Duration = Duration::from_secs(60);
+const MAX_CRASH_BACKOFF: Duration = Duration::from_secs(30);
 
 struct ServerState {
-    server_process: Mutex<Option<Child>>,
+    // Shared with the panic hook so a crash still takes the server down
+    server_process: Arc<Mutex<Option<ManagedServer>>>,
+    // PID of a server a previous run left behind that we took over instead
+    // of spawning one; stopped with the app like a child would be
+    adopted_pid: Mutex<Option<u32>>,
+    // The port we asked for, until the stdout reader sees the one the
+    // server reports it listens on
+    port: Arc<Mutex<Option<u16>>>,
+    server_dir: Mutex<Option<PathBuf>>,
+    restarting: AtomicBool,
+    // Wall-clock time for display, Instant for uptime that survives clock changes
+    started_at: Mutex<Option<(SystemTime, Instant)>>,
+    restart_count: AtomicU32,
+    // Last lines the server wrote to stderr, for startup failure reports
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
+    // Set by the stdout reader once the current server prints its ready line
+    ready_line: Arc<Mutex<Option<ReadyLine>>>,
+    startup: Mutex<Option<StartupTiming>>,
+    // Recent stdout and stderr lines across restarts, for `get_recent_logs`
+    server_logs: Arc<Mutex<LogBuffer>>,
+    // Opened during setup, once the app log dir is known
+    server_log: Mutex<Option<Arc<ServerLog>>>,
+    node: Mutex<Option<NodeRuntime>>,
+    // Certificate to serve with when OLLY_HTTPS is on, and whether the
+    // current server actually came up with it
+    tls: Mutex<Option<TlsFiles>>,
+    https: AtomicBool,
+    // Writable dirs handed to the server, resolved during setup
+    data_dir: Mutex<Option<PathBuf>>,
+    cache_dir: Mutex<Option<PathBuf>>,
+    // Set once the app starts quitting, so background startup and crash
+    // restarts stop instead of leaving an orphaned server behind
+    shutting_down: AtomicBool,
+    // Servers started with `start_named_server`, by name. The bundled server
+    // above is the "default" one and is never in here.
+    servers: Mutex<HashMap<String, ServerHandle>>,
+    // Previous CPU sample of the server, and which `watch_server_stats`
+    // call the `server-stats` stream belongs to
+    stats: Mutex<StatsSampler>,
+    stats_stream: AtomicU64,
+}
+
+/// A server started with `start_named_server`, running alongside the
+/// bundled one on a port of its own. Not restarted if it crashes.
+struct ServerHandle {
+    child: ManagedServer,
+    port: u16,
+    server_dir: PathBuf,
+}
+
+impl ServerState {
+    /// Call whenever a freshly spawned child is stored in `server_process`.
+    fn record_start(&self, is_restart: bool) {
+        *self.started_at.lock().unwrap() = Some((SystemTime::now(), Instant::now()));
+        if is_restart {
+            self.restart_count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+    
+    /// How the probes reach the bundled server: over HTTPS, trusting the
+    /// app's certificate, once it serves HTTPS.
+    fn scheme(&self) -> Scheme {
+        if !self.https.load(Ordering::SeqCst) {
+            return Scheme::Http;
+        }
+        Scheme::Https(self.tls.lock().unwrap().as_ref().map(|tls| tls.cert.clone()))
+    }
+    
+    /// Stores a freshly spawned child, unless the app started quitting in the
+    /// meantime, in which case the child is stopped and `Cancelled` returned.
+    fn adopt_child(&self, child: Child, is_restart: bool) -> Result<(), ServerError> {
+        let mut child = ManagedServer(child);
+        let mut server = self.server_process.lock().unwrap();
+        if self.shutting_down.load(Ordering::SeqCst) {
+            drop(server);
+            log::info!("App is quitting, stopping the server that was just started");
+            let _ = terminate_gracefully(&mut child, shutdown_grace_period());
+            return Err(ServerError::Cancelled);
+        }
+        if let Some(previous) = server.replace(child) {
+            // Callers stop the old server first; dropping it kills it if one
+            // slipped through anyway
+            log::warn!("Replacing server PID {} that was still tracked", previous.id());
+        }
+        drop(server);
+        self.record_start(is_restart);
+        Ok(())
+    }
+    
+    /// Whether the child is still alive (checked with `try_wait`, so an exit
+    /// is noticed before anyone reaps it) and its PID.
+    fn child_status(&self) -> (bool, Option<u32>) {
+        match self.server_process.lock().unwrap().as_mut() {
+            Some(child) => (matches!(child.try_wait(), Ok(None)), Some(child.id())),
+            None => match *self.adopted_pid.lock().unwrap() {
+                Some(pid) => (process_alive(pid), Some(pid)),
+                None => (false, None),
+            },
+        }
+    }
+}
+
+/// Locates the Next.js standalone output. `OLLY_SERVER_DIR` takes precedence
+/// so a locally built server can be run without rebuilding the app.
+fn find_server_dir(app: &tauri::AppHandle) -> Result<PathBuf, ServerError> {
+    let mut rejected_override = None;
+    if let Some(override_dir) = std::env::var_os("OLLY_SERVER_DIR") {
+        let server_dir = PathBuf::from(override_dir);
+        if server_dir.is_dir() {
+            log::info!("Using server directory override OLLY_SERVER_DIR={:?}", server_dir);
+            return Ok(server_dir);
+        }
+        log::warn!("Ignoring OLLY_SERVER_DIR={:?}: not a directory", server_dir);
+        rejected_override = Some(server_dir);
+    }
+    
+    let resource_dir = app.path().resource_dir().ok();
+    let exe_path = std::env::current_exe().ok();
+    let exe_dir = exe_path.as_deref().and_then(Path::parent);
+    let mut result = find_server_dir_in(resource_dir.as_deref(), exe_dir, &server_subdir());
+    match &mut result {
+        Ok(server_dir) => log::info!("Found server directory: {:?}", server_dir),
+        Err(ServerError::ServerDirNotFound { searched }) => {
+            if let Some(dir) = rejected_override {
+                searched.insert(0, dir);
+            }
+            log::error!("Could not find server directory, searched: {:?}", searched);
+        }
+        Err(_) => {}
+    }
+    result
+}
+
+/// Looks for `subdir` in `resource_dir` first (production), then in the
+/// locations relative to the directory of the executable.
+fn find_server_dir_in(
+    resource_dir: Option<&Path>,
+    exe_dir: Option<&Path>,
+    subdir: &Path,
+) -> Result<PathBuf, ServerError> {
+    let mut roots: Vec<PathBuf> = resource_dir.map(Path::to_path_buf).into_iter().collect();
+    if let Some(exe_dir) = exe_dir {
+        roots.extend(exe_resource_dirs(exe_dir));
+    }
+    resolve_server_dir(&roots, subdir).ok_or_else(|| ServerError::ServerDirNotFound {
+        searched: roots.iter().map(|root| root.join(subdir)).collect(),
+    })
+}
+
+/// Name of the server directory inside the bundled resources, `server` unless
+/// `OLLY_SERVER_SUBDIR` names another relative path.
+fn server_subdir() -> PathBuf {
+    let Some(value) = std::env::var_os("OLLY_SERVER_SUBDIR") else {
+        return PathBuf::from(DEFAULT_SERVER_SUBDIR);
+    };
+    let subdir = PathBuf::from(&value);
+    // Must stay inside the resource dir
+    let inside = subdir.components().all(|part| matches!(part, std::path::Component::Normal(_)));
+    if subdir.as_os_str().is_empty() || !inside {
+        log::warn!("Ignoring OLLY_SERVER_SUBDIR={:?}, using {:?}", value, DEFAULT_SERVER_SUBDIR);
+        return PathBuf::from(DEFAULT_SERVER_SUBDIR);
+    }
+    subdir
+}
+
+/// Picks `<root>/<subdir>` from the first candidate root whose copy can be
+/// launched (see `launch_strategy`). Failing that, returns the first such
+/// directory that exists at all, so it is reported as incomplete rather than
+/// missing.
+fn resolve_server_dir(candidates: &[PathBuf], subdir: &Path) -> Option<PathBuf> {
+    let server_dirs: Vec<PathBuf> = candidates.iter().map(|root| root.join(subdir)).collect();
+    server_dirs
+        .iter()
+        .find(|dir| launch_strategy(dir).is_some())
+        .or_else(|| server_dirs.iter().find(|dir| dir.is_dir()))
+        .cloned()
+}
+
+/// Directories that may hold bundled resources, relative to the directory
+/// containing the executable.
+#[cfg(windows)]
+fn exe_resource_dirs(app_dir: &Path) -> Vec<PathBuf> {
+    // The Windows installers put resources right next to the executable
+    vec![app_dir.to_path_buf()]
+}
+
+#[cfg(target_os = "linux")]
+fn exe_resource_dirs(app_dir: &Path) -> Vec<PathBuf> {
+    let appdir = std::env::var_os("APPDIR").map(PathBuf::from);
+    linux_resource_dirs(app_dir, appdir.as_deref())
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn exe_resource_dirs(app_dir: &Path) -> Vec<PathBuf> {
+    macos_resource_dirs(app_dir)
+}
+
+/// macOS: Contents/MacOS -> Contents/Resources
+#[cfg(any(not(any(windows, target_os = "linux")), test))]
+fn macos_resource_dirs(app_dir: &Path) -> Vec<PathBuf> {
+    app_dir.parent().map(|p| p.join("Resources")).into_iter().collect()
+}
+
+/// Names the Linux bundles install resources under: the product name for
+/// Tauri's deb/rpm/AppImage layouts, the crate name for hand-rolled packages.
+#[cfg(target_os = "linux")]
+const LINUX_BUNDLE_NAMES: [&str; 2] = ["Olly Molly", env!("CARGO_PKG_NAME")];
+
+/// Linux resource locations for an executable in `app_dir`. Takes the
+/// AppImage mount point explicitly rather than reading `APPDIR` so the layout
+/// logic doesn't depend on the environment.
+#[cfg(target_os = "linux")]
+fn linux_resource_dirs(app_dir: &Path, appdir: Option<&Path>) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    for name in LINUX_BUNDLE_NAMES {
+        // deb/rpm: /usr/bin/<exe> -> /usr/lib/<name>
+        if let Some(prefix) = app_dir.parent() {
+            dirs.push(prefix.join("lib").join(name));
+        }
+        // AppImage: $APPDIR/usr/bin/<exe>, resources in $APPDIR/usr/lib/<name>
+        if let Some(appdir) = appdir {
+            dirs.push(appdir.join("usr").join("lib").join(name));
+        }
+    }
+    // Tarballs and portable builds keep resources next to the executable
+    dirs.push(app_dir.to_path_buf());
+    dirs
+}
+
+/// The port to prefer, from the first valid one of: a `--port` flag,
+/// `OLLY_PORT`, the `port` key in `config.json` in the app config dir, and
+/// `DEFAULT_PORT`. `0` means "let the OS pick one". If the port turns out to
+/// be taken, `launch_on_free_port` moves to a free one.
+fn configured_port(app: &tauri::AppHandle) -> u16 {
+    let config_port = match app.path().app_config_dir() {
+        Ok(dir) => config_file_port(&dir.join(APP_CONFIG_FILE)),
+        Err(_) => None,
+    };
+    let (port, source) = choose_port([
+        ("--port", port_arg(std::env::args())),
+        ("OLLY_PORT", std::env::var("OLLY_PORT").ok()),
+        (APP_CONFIG_FILE, config_port),
+    ]);
+    log::info!("Preferring port {} (from {})", port, source);
+    port
+}
+
+/// Takes the first candidate that parses as a usable port, warning about the
+/// ones that don't. Returns the port and the name of its source.
+fn choose_port<'a>(
+    candidates: impl IntoIterator<Item = (&'a str, Option<String>)>,
+) -> (u16, &'a str) {
+    for (source, value) in candidates {
+        let Some(value) = value else { continue };
+        match parse_port(&value) {
+            Ok(port) => return (port, source),
+            Err(reason) => log::warn!("Ignoring port {:?} from {}: {}", value, source, reason),
+        }
+    }
+    (DEFAULT_PORT, "default")
+}
+
+fn parse_port(value: &str) -> Result<u16, &'static str> {
+    let port: u64 = value.trim().parse().map_err(|_| "not a number")?;
+    match port {
+        0 => Ok(0),
+        // Binding these needs root on Unix
+        1..=1023 => Err("privileged port"),
+        1024..=65535 => Ok(port as u16),
+        _ => Err("out of range"),
+    }
+}
+
+/// The value of `--port 4567` or `--port=4567` among the app's arguments.
+fn port_arg(mut args: impl Iterator<Item = String>) -> Option<String> {
+    while let Some(arg) = args.next() {
+        if arg == "--port" {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix("--port=") {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+/// The `port` key of the app config file, as a number or a string. A missing
+/// file or key means none; a malformed file is logged and ignored.
+fn config_file_port(path: &Path) -> Option<String> {
+    config_file_value(path, "port")
+}
+
+/// The value of `key` in the app config file, as a number or a string.
+fn config_file_value(path: &Path, key: &str) -> Option<String> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let config: serde_json::Value = match serde_json::from_str(&contents) {
+        Ok(config) => config,
+        Err(e) => {
+            log::warn!("Ignoring malformed {:?}: {}", path, e);
+            return None;
+        }
+    };
+    match config.get(key)? {
+        serde_json::Value::String(value) => Some(value.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+/// Interface the server binds to, from `OLLY_HOSTNAME` or its shorter alias
+/// `OLLY_HOST` (e.g. `0.0.0.0` in a dev container). Defaults to 127.0.0.1
+/// rather than `localhost`, which some machines resolve oddly or to ::1 only.
+/// The readiness probe and the webview URL derive from the same value.
+fn server_hostname() -> String {
+    ["OLLY_HOSTNAME", "OLLY_HOST"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .map(|value| value.trim().to_string())
+        .find(|value| !value.is_empty())
+        .unwrap_or_else(|| DEFAULT_HOSTNAME.to_string())
+}
+
+/// Host to connect to for a server bound to `hostname`; wildcard binds are
+/// reached over loopback.
+fn connect_host(hostname: &str) -> &str {
+    match hostname {
+        "0.0.0.0" => "127.0.0.1",
+        "::" => "::1",
+        _ => hostname,
+    }
+}
+
+/// How the readiness and health probes talk to a server.
+#[derive(Debug, Clone, PartialEq)]
+enum Scheme {
+    Http,
+    /// HTTPS, accepting only this certificate if given
+    Https(Option<PathBuf>),
+}
+
+/// URL the webview loads for the server on `port`. Everything that needs the
+/// server's address goes through this, so scheme and host live here only.
+fn server_url(port: u16, https: bool) -> String {
+    let scheme = if https { "https" } else { "http" };
+    let hostname = server_hostname();
+    let host = connect_host(&hostname);
+    if host.contains(':') {
+        format!("{}://[{}]:{}", scheme, host, port)
+    } else {
+        format!("{}://{}:{}", scheme, host, port)
+    }
+}
+
+/// Where the UI lives on the server, from `OLLY_BASE_PATH` (e.g. `/app` for
+/// a Next.js `basePath`), `/` by default.
+fn base_path() -> String {
+    normalize_base_path(&std::env::var("OLLY_BASE_PATH").unwrap_or_default())
+}
+
+/// `/` or a path with a leading and no trailing slash, the form Next.js
+/// expects for `basePath`.
+fn normalize_base_path(raw: &str) -> String {
+    let path = raw.trim().trim_matches('/');
+    if path.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{}", path)
+    }
+}
+
+/// Returns `preferred` if nothing is listening on it, otherwise a free port
+/// assigned by the OS (`preferred == 0` always asks the OS). The probe
+/// listener is dropped before returning so Node can bind the port itself.
+fn find_free_port(preferred: u16) -> u16 {
+    let hostname = server_hostname();
+    if preferred != 0 && TcpListener::bind((hostname.as_str(), preferred)).is_ok() {
+        return preferred;
+    }
+    
+    match TcpListener::bind((hostname.as_str(), 0)).and_then(|listener| listener.local_addr()) {
+        Ok(addr) => {
+            if preferred != 0 {
+                // Name the culprit, so "my server moved" has an answer in the log
+                log::warn!(
+                    "Port {} is in use by {}, falling back to port {}",
+                    preferred,
+                    port_holder(preferred).unwrap_or_else(|| "another program".to_string()),
+                    addr.port()
+                );
+            }
+            addr.port()
+        }
+        Err(e) => {
+            log::error!("Failed to find a free port, trying {} anyway: {}", preferred, e);
+            preferred
+        }
+    }
+}
+
+/// How the server in a directory is started.
+#[derive(Debug, Clone, PartialEq)]
+enum LaunchStrategy {
+    /// `node server.js`, for the standalone output
+    Standalone(PathBuf),
+    /// `node <next bin> start`, for builds without standalone output that
+    /// ship their `package.json` and `node_modules` instead
+    NextStart(PathBuf),
+}
+
+/// The standalone `server.js` if there is one, otherwise `next start` if the
+/// directory has a `package.json` and Next.js installed.
+fn launch_strategy(server_dir: &Path) -> Option<LaunchStrategy> {
+    let server_js = server_dir.join("server.js");
+    if server_js.is_file() {
+        return Some(LaunchStrategy::Standalone(server_js));
+    }
+    let next_bin = server_dir.join("node_modules").join("next").join("dist").join("bin").join("next");
+    if server_dir.join("package.json").is_file() && next_bin.is_file() {
+        return Some(LaunchStrategy::NextStart(next_bin));
+    }
+    None
+}
+
+fn start_next_server(
+    app: &tauri::AppHandle,
+    state: &ServerState,
+    server_dir: PathBuf,
+    port: u16,
+    extra_env: &HashMap<String, String>,
+) -> Result<Child, ServerError> {
+    log::info!("Starting Next.js server from: {:?}", server_dir);
+    
+    let Some(strategy) = launch_strategy(&server_dir) else {
+        let server_js = server_dir.join("server.js");
+        log::error!("server.js not found at {:?}, and no package.json with Next.js installed", server_js);
+        return Err(ServerError::ServerJsMissing(server_js));
+    };
+    
+    let node = find_node_binary(&server_dir, min_node_version())?;
+    let node_path = node.path.clone();
+    emit_progress(app, "node_found", Some(format!("v{} at {}", node.version, node_path.display())));
+    *state.node.lock().unwrap() = Some(node);
+    
+    let preload = server_dir.join(HTTPS_PRELOAD);
+    let tls = state.tls.lock().unwrap().clone().filter(|_| {
+        let found = preload.is_file();
+        if !found {
+            log::warn!("{:?} is missing, serving over HTTP", preload);
+        }
+        found
+    });
+    state.https.store(tls.is_some(), Ordering::SeqCst);
+    
+    let data_dir = state.data_dir.lock().unwrap().clone();
+    let cache_dir = state.cache_dir.lock().unwrap().clone();
+    let command = server_command(
+        &node_path,
+        &strategy,
+        &server_dir,
+        port,
+        extra_env,
+        (data_dir.as_deref(), cache_dir.as_deref()),
+        tls.as_ref().map(|tls| (preload.as_path(), tls)),
+    );
+    let mut child = spawn_server(command, extra_env)?;
+    
+    log::info!("Next.js server started with PID: {} on port {}", child.id(), port);
+    emit_progress(app, "spawned", Some(format!("PID {}", child.id())));
+    forward_server_output(&mut child, state, DEFAULT_SERVER_NAME);
+    Ok(child)
+}
+
+/// Node running the server in `server_dir` on `port` the way `strategy`
+/// says, with `extra_env`, the writable data and cache dirs, and with `tls`
+/// the HTTPS preload and certificate.
+fn server_command(
+    node_path: &Path,
+    strategy: &LaunchStrategy,
+    server_dir: &Path,
+    port: u16,
+    extra_env: &HashMap<String, String>,
+    (data_dir, cache_dir): (Option<&Path>, Option<&Path>),
+    tls: Option<(&Path, &TlsFiles)>,
+) -> Command {
+    let mut command = command(node_path);
+    // Its own process group, so stopping the server reaches the workers it
+    // spawns too. It also keeps a Ctrl+C in the terminal of `tauri dev` from
+    // killing the server behind the app's back.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let node_args = node_args();
+    let node_env = node_env(extra_env);
+    log::info!("Server NODE_ENV={}", node_env);
+    // So a debugger can attach to a development server. Port 0 gives each
+    // server, named ones included, a free port of its own, which node
+    // prints to the server log as "Debugger listening on ws://127.0.0.1:...".
+    if node_env == "development" && !node_args.iter().any(|arg| arg.starts_with("--inspect")) {
+        command.arg("--inspect=0");
+    }
+    command.args(node_args);
+    if let Some((preload, _)) = tls {
+        command.arg("--require").arg(preload);
+    }
+    match strategy {
+        LaunchStrategy::Standalone(server_js) => {
+            log::info!("Launching the standalone server.js");
+            command.arg(server_js);
+        }
+        LaunchStrategy::NextStart(next_bin) => {
+            // `next start` ignores HOSTNAME, so pass the address explicitly
+            log::info!("No server.js, launching with next start");
+            command
+                .arg(next_bin)
+                .args(["start", "-p", &port.to_string(), "-H", &server_hostname()]);
+        }
+    }
+    command
+        .current_dir(server_dir)
+        // Before PORT and HOSTNAME, so a stray entry can't move the server
+        // away from the address readiness is checked on
+        .envs(extra_env)
+        .env("NODE_ENV", &node_env)
+        .env("PORT", port.to_string())
+        .env("HOSTNAME", server_hostname());
+    // The cwd is the bundled resources, which may be read-only (and on macOS
+    // is inside the signed bundle), so the server writes elsewhere: the
+    // database and anything else that must persist under OLLY_DATA_DIR,
+    // anything it can rebuild under OLLY_CACHE_DIR. Both exist already.
+    if let Some(data_dir) = data_dir {
+        command.env("OLLY_DATA_DIR", data_dir);
+    }
+    if let Some(cache_dir) = cache_dir {
+        command.env("OLLY_CACHE_DIR", cache_dir);
+    }
+    if let Some((_, tls)) = tls {
+        command.env("HTTPS", "1").env("OLLY_TLS_CERT", &tls.cert).env("OLLY_TLS_KEY", &tls.key);
+    }
+    command
+}
+
+/// Spawns `command` with piped output, logging it first with the
+/// `extra_env` values hidden.
+fn spawn_server(mut command: Command, extra_env: &HashMap<String, String>) -> Result<Child, ServerError> {
+    let launch = describe_command(&command, extra_env);
+    log::info!("Launching {}", launch);
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    retry_spawn(SPAWN_ATTEMPTS, spawn_backoff, || command.spawn()).map_err(|e| {
+        log::error!("Failed to start server ({}): {}", launch, e);
+        ServerError::SpawnFailed(e)
+    })
+}
+
+/// Calls `spawn` up to `attempts` times, waiting `backoff(n)` before retry
+/// `n`. Right after an install, antivirus scanning the new files or a
+/// lingering file lock can make the first spawns fail. A program that is
+/// missing (`NotFound`) won't turn up by waiting, so that fails straight away.
+fn retry_spawn<T>(
+    attempts: usize,
+    backoff: impl Fn(usize) -> Duration,
+    mut spawn: impl FnMut() -> std::io::Result<T>,
+) -> std::io::Result<T> {
+    let mut attempt = 1;
+    loop {
+        match spawn() {
+            Err(e) if attempt < attempts && e.kind() != std::io::ErrorKind::NotFound => {
+                let delay = backoff(attempt);
+                log::warn!("Spawn attempt {} of {} failed: {}, retrying in {:?}", attempt, attempts, e, delay);
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            Ok(child) => {
+                if attempt > 1 {
+                    log::info!("Spawned the server on attempt {} of {}", attempt, attempts);
+                }
+                return Ok(child);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// 250ms, 500ms, 1s, ... before the first, second, third retry.
+fn spawn_backoff(retry: usize) -> Duration {
+    let exponent = retry.saturating_sub(1).min(16) as u32;
+    SPAWN_RETRY_DELAY * 2u32.pow(exponent)
+}
+
+/// Program, arguments, working directory and the environment set on top of
+/// the app's own, on one line for user-submitted logs. Values from
+/// `server-env.json` (`masked`) are left out.
+fn describe_command(command: &Command, masked: &HashMap<String, String>) -> String {
+    let mut description = format!("{:?}", command.get_program());
+    for arg in command.get_args() {
+        description.push_str(&format!(" {:?}", arg));
+    }
+    if let Some(dir) = command.get_current_dir() {
+        description.push_str(&format!(" in {:?}", dir));
+    }
+    let mut envs: Vec<String> = command
+        .get_envs()
+        .map(|(name, value)| {
+            let name = name.to_string_lossy();
+            match value {
+                _ if masked.contains_key(name.as_ref()) => format!("{}=<hidden>", name),
+                Some(value) => format!("{}={:?}", name, value),
+                None => format!("{} unset", name),
+            }
+        })
+        .collect();
+    envs.sort();
+    if !envs.is_empty() {
+        description.push_str(&format!(" with {}", envs.join(" ")));
+    }
+    description
+}
+
+/// Extra environment variables for the server, from `server-env.json` in the
+/// app config dir and then `OLLY_ENV_*` variables of the app's environment,
+/// which win over the file. Read on every launch, so edits apply on the next
+/// restart. PORT and HOSTNAME are set after these and can't be overridden.
+fn server_env(app: &tauri::AppHandle) -> HashMap<String, String> {
+    let mut env = match app.path().app_config_dir() {
+        Ok(dir) => load_server_env(&dir.join(SERVER_ENV_FILE)),
+        Err(e) => {
+            log::warn!("No app config directory, not reading {}: {}", SERVER_ENV_FILE, e);
+            HashMap::new()
+        }
+    };
+    let passthrough = prefixed_env(
+        std::env::vars_os().filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?))),
+    );
+    if !passthrough.is_empty() {
+        let mut names: Vec<&str> = passthrough.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        log::info!("Passing {:?} from OLLY_ENV_* to the server", names);
+    }
+    env.extend(passthrough);
+    env
+}
+
+/// `OLLY_ENV_FOO=bar` as `FOO=bar`, for feature flags and the like that the
+/// server should see without editing `server-env.json`.
+fn prefixed_env(vars: impl IntoIterator<Item = (String, String)>) -> HashMap<String, String> {
+    vars.into_iter()
+        .filter_map(|(name, value)| {
+            let name = name.strip_prefix("OLLY_ENV_").filter(|name| !name.is_empty())?;
+            Some((name.to_string(), value))
+        })
+        .collect()
+}
+
+/// Extra arguments for node itself from `OLLY_NODE_ARGS`, split on
+/// whitespace, e.g. `--max-old-space-size=2048`.
+fn node_args() -> Vec<String> {
+    std::env::var("OLLY_NODE_ARGS")
+        .map(|args| args.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// `NODE_ENV` for the server: `OLLY_NODE_ENV` if set, else the one from
+/// `extra_env`, else `production`. The standalone server.js sets
+/// `NODE_ENV=production` itself before it loads Next.js, so this doesn't
+/// turn on development errors; `development` only adds `--inspect=0`.
+fn node_env(extra_env: &HashMap<String, String>) -> String {
+    choose_node_env(std::env::var("OLLY_NODE_ENV").ok(), extra_env.get("NODE_ENV").map(String::as_str))
+}
+
+fn choose_node_env(override_value: Option<String>, configured: Option<&str>) -> String {
+    override_value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .or_else(|| configured.map(str::to_string))
+        .unwrap_or_else(|| "production".to_string())
+}
+
+/// Parses a flat JSON object of strings. A missing file means no extra
+/// variables; an unreadable or malformed one is logged and ignored rather
+/// than failing startup.
+fn load_server_env(path: &Path) -> HashMap<String, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return HashMap::new(),
+        Err(e) => {
+            log::warn!("Ignoring {:?}: {}", path, e);
+            return HashMap::new();
+        }
+    };
+    match serde_json::from_str::<HashMap<String, String>>(&contents) {
+        Ok(env) => {
+            // Names only; the values are likely to be secrets
+            let mut names: Vec<&str> = env.keys().map(String::as_str).collect();
+            names.sort_unstable();
+            log::info!("Passing {:?} from {:?} to the server", names, path);
+            env
+        }
+        Err(e) => {
+            log::warn!("Ignoring malformed {:?}: {}", path, e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Drains the child's stdout and stderr into the app log. Nothing else reads
+/// these pipes, so without this the server blocks once a pipe buffer fills.
+/// The reader threads exit on their own when the child closes its end.
+/// Only the bundled server (`name` is `DEFAULT_SERVER_NAME`) has its ready
+/// line and stderr tail tracked; named servers are just logged.
+fn forward_server_output(child: &mut Child, state: &ServerState, name: &str) {
+    // Thread names carry the PID so readers left over from a previous
+    // instance can be told apart in the log
+    let pid = child.id();
+    let is_default = name == DEFAULT_SERVER_NAME;
+    let prefix = if is_default { "[next]".to_string() } else { format!("[next:{}]", name) };
+    let server_log = state.server_log.lock().unwrap().clone();
+    if let Some(stdout) = child.stdout.take() {
+        let server_logs = Arc::clone(&state.server_logs);
+        let server_log = server_log.clone();
+        let ready_line = is_default.then(|| Arc::clone(&state.ready_line));
+        if let Some(ready_line) = &ready_line {
+            ready_line.lock().unwrap().take();
+        }
+        let port = is_default.then(|| Arc::clone(&state.port));
+        let pattern = ready_pattern();
+        let prefix = prefix.clone();
+        spawn_line_reader(format!("next-stdout-{}", pid), stdout, move |line| {
+            log::log!(server_line_level(line, log::Level::Info), "{} {}", prefix, line);
+            if let Some(ready_line) = &ready_line {
+                if let Some(parsed) = parse_ready_line(&pattern, line) {
+                    ready_line.lock().unwrap().get_or_insert(parsed);
+                }
+            }
+            if let (Some(port), Some(listening)) = (&port, parse_listening_port(line)) {
+                let mut port = port.lock().unwrap();
+                if *port != Some(listening) {
+                    log::info!("Server reports listening on port {} (started with {:?})", listening, *port);
+                    *port = Some(listening);
+                }
+            }
+            server_logs.lock().unwrap().push("stdout", pid, line);
+            if let Some(server_log) = &server_log {
+                server_log.write_line("stdout", pid, line);
+            }
+        });
+    }
+    if let Some(stderr) = child.stderr.take() {
+        let server_logs = Arc::clone(&state.server_logs);
+        let stderr_tail = is_default.then(|| Arc::clone(&state.stderr_tail));
+        if let Some(stderr_tail) = &stderr_tail {
+            stderr_tail.lock().unwrap().clear();
+        }
+        spawn_line_reader(format!("next-stderr-{}", pid), stderr, move |line| {
+            log::log!(server_line_level(line, log::Level::Warn), "{} {}", prefix, line);
+            server_logs.lock().unwrap().push("stderr", pid, line);
+            if let Some(stderr_tail) = &stderr_tail {
+                push_capped(&mut stderr_tail.lock().unwrap(), line, STDERR_TAIL_LINES);
+            }
+            if let Some(server_log) = &server_log {
+                server_log.write_line("stderr", pid, line);
+            }
+        });
+    }
+}
+
+/// What the server said when it announced it was ready.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ReadyLine {
+    /// Startup time as reported by Next.js, if the pattern captured one
+    reported: Option<Duration>,
+}
+
+/// How long the current server took to start, shown in `server_status`.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+struct StartupTiming {
+    /// From spawning Node to the server being ready, as measured here
+    measured_ms: u64,
+    /// The time Next.js printed in its ready line, if it printed one
+    reported_ms: Option<u64>,
+    /// `"ready_line"` or `"http"`, whichever noticed first
+    signal: &'static str,
+}
+
+/// The ready line pattern, from `OLLY_READY_PATTERN` if that is a valid
+/// regex. An optional number group followed by an `ms`/`s` unit group is
+/// read as the startup time.
+fn ready_pattern() -> Regex {
+    if let Ok(pattern) = std::env::var("OLLY_READY_PATTERN") {
+        match Regex::new(&pattern) {
+            Ok(regex) => return regex,
+            Err(e) => log::warn!("Ignoring invalid OLLY_READY_PATTERN={:?}: {}", pattern, e),
+        }
+    }
+    Regex::new(DEFAULT_READY_PATTERN).expect("default ready pattern is valid")
+}
+
+fn parse_ready_line(pattern: &Regex, line: &str) -> Option<ReadyLine> {
+    let captures = pattern.captures(line)?;
+    let amount = captures.get(1).and_then(|amount| amount.as_str().parse::<f64>().ok());
+    let reported = amount.and_then(|amount| {
+        let secs = match captures.get(2).map(|unit| unit.as_str()) {
+            Some("s") => amount,
+            _ => amount / 1000.0,
+        };
+        Duration::try_from_secs_f64(secs).ok()
+    });
+    Some(ReadyLine { reported })
+}
+
+/// The port in the address Next.js prints once it listens, e.g.
+/// `- Local:        http://localhost:1234` (or `started server on
+/// 0.0.0.0:3000` from older releases). Lines that don't announce the local
+/// address, including `- Network:`, give `None`.
+fn parse_listening_port(line: &str) -> Option<u16> {
+    let address = ["- Local:", "started server on", "ready on"]
+        .iter()
+        .find_map(|marker| line.split_once(marker).map(|(_, rest)| rest))?
+        .split_whitespace()
+        .next()?
+        .trim_end_matches(',');
+    let authority = address.split_once("://").map_or(address, |(_, rest)| rest);
+    let authority = authority.split('/').next()?;
+    authority.rsplit_once(':')?.1.parse().ok()
+}
+
+/// Appends `line`, dropping the oldest lines beyond `capacity`.
+fn push_capped(lines: &mut VecDeque<String>, line: &str, capacity: usize) {
+    if lines.len() == capacity {
+        lines.pop_front();
+    }
+    lines.push_back(line.to_string());
+}
+
+/// Maps a line of server output to a log level. Next.js marks warnings with
+/// `⚠` and errors with `⨯`; anything else keeps its stream's default.
+fn server_line_level(line: &str, default: log::Level) -> log::Level {
+    let line = line.trim_start();
+    if line.starts_with('⨯') || line.starts_with("Error") || line.contains("Error:") {
+        log::Level::Error
+    } else if line.starts_with('⚠') {
+        log::Level::Warn
+    } else {
+        default
+    }
+}
+
+/// Calls `on_line` for every line read from `pipe` until it reaches EOF,
+/// which happens once the process holding the write end exits.
+fn spawn_line_reader<R, F>(name: String, pipe: R, mut on_line: F)
+where
+    R: Read + Send + 'static,
+    F: FnMut(&str) + Send + 'static,
+{
+    let spawned = std::thread::Builder::new().name(name.clone()).spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            // read_until rather than lines(): one invalid UTF-8 byte from the
+            // server must not stop us draining the pipe
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => on_line(String::from_utf8_lossy(&line).trim_end()),
+            }
+        }
+        log::info!("{} reached end of output", std::thread::current().name().unwrap_or("reader"));
+    });
+    if let Err(e) = spawned {
+        log::error!("Failed to spawn {} reader thread: {}", name, e);
+    }
+}
+
+/// The route readiness is checked on, from `OLLY_HEALTH_PATH`, under the
+/// base path like every other route.
+fn health_path() -> String {
+    let path = std::env::var("OLLY_HEALTH_PATH")
+        .ok()
+        .map(|path| path.trim().to_string())
+        .filter(|path| !path.is_empty())
+        .unwrap_or_else(|| DEFAULT_HEALTH_PATH.to_string());
+    under_base_path(&path)
+}
+
+/// `path` (with a leading slash) on a server using `OLLY_BASE_PATH`.
+fn under_base_path(path: &str) -> String {
+    let path = format!("/{}", path.trim_start_matches('/'));
+    match base_path().as_str() {
+        "/" => path,
+        base => format!("{}{}", base, path),
+    }
+}
+
+/// Whether the server at `addr` is ready: its health route answers 200 with
+/// `ok`. Before the API routes are warmed up a page may already render, so
+/// `/` is only asked for apps without the route (it 404s), and then any
+/// status will do.
+fn probe_http(addr: &SocketAddr, scheme: &Scheme) -> bool {
+    match http_get(addr, &health_path(), scheme) {
+        Some((200, body)) => is_ok_body(&body),
+        Some((404, _)) => http_get(addr, "/", scheme).is_some(),
+        _ => false,
+    }
+}
+
+/// `ok`, either plain or as a JSON string.
+fn is_ok_body(body: &str) -> bool {
+    matches!(body.trim(), "ok" | "\"ok\"")
+}
+
+/// Issues a bare `GET path` and returns the status and body of the answer,
+/// if anything answered with HTTP.
+fn http_get(addr: &SocketAddr, path: &str, scheme: &Scheme) -> Option<(u16, String)> {
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, addr);
+    if let Scheme::Https(cert) = scheme {
+        let response =
+            https_exchange(addr, &request, cert.as_deref(), READY_PROBE_TIMEOUT, MAX_PROBE_RESPONSE_BYTES)?;
+        return parse_http_response(&response);
+    }
+    
+    let mut stream = TcpStream::connect_timeout(addr, READY_PROBE_TIMEOUT).ok()?;
+    let _ = stream.set_read_timeout(Some(READY_PROBE_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(READY_PROBE_TIMEOUT));
+    stream.write_all(request.as_bytes()).ok()?;
+    
+    // Connection: close, so the body ends at EOF; a timeout keeps what came
+    let mut response = Vec::new();
+    let _ = stream.take(MAX_PROBE_RESPONSE_BYTES).read_to_end(&mut response);
+    parse_http_response(&response)
+}
+
+/// Status code and body of a raw HTTP/1.1 response, undoing chunked
+/// transfer encoding, which Node uses for bodies without a length.
+fn parse_http_response(response: &[u8]) -> Option<(u16, String)> {
+    let response = String::from_utf8_lossy(response);
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let mut lines = head.lines();
+    let status_line = lines.next()?;
+    if !status_line.starts_with("HTTP/") {
+        return None;
+    }
+    let status = status_line.split_whitespace().nth(1)?.parse().ok()?;
+    let chunked = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("transfer-encoding") && value.trim().eq_ignore_ascii_case("chunked")
+        })
+    });
+    if !chunked {
+        return Some((status, body.to_string()));
+    }
+    
+    let mut decoded = String::new();
+    let mut rest = body;
+    while let Some((size, after)) = rest.split_once("\r\n") {
+        let size = usize::from_str_radix(size.split(';').next().unwrap_or_default().trim(), 16).ok()?;
+        if size == 0 || after.len() < size {
+            break;
+        }
+        decoded.push_str(after.get(..size)?);
+        rest = after[size..].trim_start_matches("\r\n");
+    }
+    Some((status, decoded))
+}
+
+/// Whether the server on `port` is serving.
+fn server_responds(port: u16, scheme: &Scheme) -> bool {
+    host_responds(connect_host(&server_hostname()), port, scheme)
+}
+
+/// Whether whatever listens on `host:port` is serving, asked over HTTPS
+/// when `scheme` says so.
+fn host_responds(host: &str, port: u16, scheme: &Scheme) -> bool {
+    // Resolve on every call: a hostname like `localhost` may map to ::1,
+    // 127.0.0.1 or both depending on the machine, and Node binds whichever it
+    // resolves first.
+    let addrs: Vec<SocketAddr> = (host, port)
+        .to_socket_addrs()
+        .map(|addrs| addrs.collect())
+        .unwrap_or_default();
+    addrs.iter().any(|addr| probe_http(addr, scheme))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReadySignal {
+    Line,
+    Http,
+}
+
+/// Waits until `announced` reports the ready line, the server answers HTTP,
+/// `timeout` passes or `give_up` returns true. The ready line is the faster
+/// signal; polling HTTP alongside it keeps startup working when a Next.js
+/// release words the line differently.
+fn wait_for_server_ready(
+    host: &str,
+    port: u16,
+    scheme: &Scheme,
+    timeout: Duration,
+    interval: Duration,
+    mut announced: impl FnMut() -> bool,
+    mut give_up: impl FnMut() -> bool,
+) -> Option<ReadySignal> {
+    let deadline = Instant::now() + timeout;
+    
+    while Instant::now() < deadline && !give_up() {
+        if announced() {
+            return Some(ReadySignal::Line);
+        }
+        if host_responds(host, port, scheme) {
+            return Some(ReadySignal::Http);
+        }
+        std::thread::sleep(interval);
+    }
+    
+    None
+}
+
+/// How often and how long to try starting the bundled server. The first
+/// boot after an install can take far longer than later ones (a slow disk,
+/// antivirus scanning every file), so a start that times out or dies is
+/// tried again.
+#[derive(Debug, Clone, PartialEq)]
+struct StartupPolicy {
+    attempts: usize,
+    /// How long each attempt may take to become ready
+    attempt_timeout: Duration,
+    /// How long all attempts together may take; the last attempt gets
+    /// whatever is left
+    budget: Duration,
+}
+
+impl StartupPolicy {
+    /// From `OLLY_STARTUP_ATTEMPTS`, `OLLY_STARTUP_TIMEOUT_MS` and
+    /// `OLLY_STARTUP_BUDGET_MS`, or else the `startup_attempts`,
+    /// `startup_timeout_ms` and `startup_budget_ms` keys of `config.json`.
+    fn load(app: &tauri::AppHandle) -> StartupPolicy {
+        let config_file = app.path().app_config_dir().ok().map(|dir| dir.join(APP_CONFIG_FILE));
+        StartupPolicy::from_settings(|var, key| {
+            std::env::var(var)
+                .ok()
+                .map(|value| (var.to_string(), value))
+                .or_else(|| Some((key.to_string(), config_file_value(config_file.as_deref()?, key)?)))
+        })
+    }
+    
+    /// `setting(env_var, config_key)` returns where a setting was found and
+    /// its value. Invalid values are logged and replaced by the default.
+    fn from_settings(setting: impl Fn(&str, &str) -> Option<(String, String)>) -> StartupPolicy {
+        let number = |var, key, default: u64| match setting(var, key) {
+            Some((source, value)) => value.trim().parse::<u64>().unwrap_or_else(|_| {
+                log::warn!("Ignoring invalid {}={:?}, using {}", source, value, default);
+                default
+            }),
+            None => default,
+        };
+        let attempts = number("OLLY_STARTUP_ATTEMPTS", "startup_attempts", DEFAULT_STARTUP_ATTEMPTS as u64);
+        let timeout_ms = DEFAULT_STARTUP_TIMEOUT.as_millis() as u64;
+        let budget_ms = DEFAULT_STARTUP_BUDGET.as_millis() as u64;
+        StartupPolicy {
+            attempts: attempts.clamp(1, 100) as usize,
+            attempt_timeout: Duration::from_millis(number("OLLY_STARTUP_TIMEOUT_MS", "startup_timeout_ms", timeout_ms)),
+            budget: Duration::from_millis(number("OLLY_STARTUP_BUDGET_MS", "startup_budget_ms", budget_ms)),
+        }
+    }
+}
+
+/// Runs `attempt` with the time it may take until it succeeds, fails in a
+/// way another try won't fix, or the policy's attempts or budget run out.
+/// The first attempt always gets the full timeout, so a budget smaller than
+/// that only rules out retries.
+/// Only timeouts and servers dying during startup are retried; a taken port
+/// is `launch_on_free_port`'s business. When several attempts failed, the
+/// error lists all of them.
+fn retry_startup<T>(
+    policy: &StartupPolicy,
+    mut attempt: impl FnMut(Duration) -> Result<T, ServerError>,
+) -> Result<T, ServerError> {
+    let started = Instant::now();
+    let mut failures = Vec::new();
+    for number in 1..=policy.attempts {
+        let remaining = policy.budget.saturating_sub(started.elapsed());
+        if number > 1 && remaining.is_zero() {
+            log::warn!("Startup budget of {:?} used up after {} attempts", policy.budget, failures.len());
+            break;
+        }
+        let timeout = if number == 1 { policy.attempt_timeout } else { policy.attempt_timeout.min(remaining) };
+        match attempt(timeout) {
+            Err(e @ (ServerError::StartupTimeout { .. } | ServerError::ServerExited { .. })) => {
+                log::warn!("Startup attempt {} of {} failed: {}", number, policy.attempts, e);
+                failures.push(e);
+            }
+            result => return result,
+        }
+    }
+    if failures.len() == 1 {
+        return Err(failures.remove(0));
+    }
+    Err(ServerError::StartupFailed { attempts: failures })
+}
+
+/// Waits up to `timeout` for the server on `port` to answer HTTP requests. If
+/// it never does, the child is killed and the error carries the tail of its
+/// stderr.
+fn await_server_ready(state: &ServerState, port: u16, timeout: Duration) -> Result<Duration, ServerError> {
+    let interval = duration_from_env("OLLY_READY_POLL_INTERVAL_MS", DEFAULT_READY_POLL_INTERVAL);
+    log::info!("Waiting up to {:?} for server to start...", timeout);
+    
+    let started = Instant::now();
+    state.startup.lock().unwrap().take();
+    // Stop early if the child dies (e.g. EADDRINUSE) instead of waiting out
+    // the whole timeout
+    let mut exit_status = None;
+    let announced = || state.ready_line.lock().unwrap().is_some();
+    let scheme = state.scheme();
+    let host = server_hostname();
+    let ready = wait_for_server_ready(connect_host(&host), port, &scheme, timeout, interval, announced, || {
+        exit_status = state
+            .server_process
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(|child| child.try_wait().ok().flatten());
+        exit_status.is_some() || state.shutting_down.load(Ordering::SeqCst)
+    });
+    if let Some(signal) = ready {
+        let elapsed = started.elapsed();
+        let reported = state.ready_line.lock().unwrap().and_then(|line| line.reported);
+        match signal {
+            ReadySignal::Line => {
+                log::info!("Server ready after {:?} (Next.js reported {:?})", elapsed, reported)
+            }
+            ReadySignal::Http => log::info!(
+                "Server ready after {:?}, answered HTTP before printing a ready line",
+                elapsed
+            ),
+        }
+        *state.startup.lock().unwrap() = Some(StartupTiming {
+            measured_ms: elapsed.as_millis() as u64,
+            reported_ms: reported.map(|reported| reported.as_millis() as u64),
+            signal: match signal {
+                ReadySignal::Line => "ready_line",
+                ReadySignal::Http => "http",
+            },
+        });
+        return Ok(elapsed);
+    }
+    if state.shutting_down.load(Ordering::SeqCst) {
+        // The exit handler stops the server itself
+        return Err(ServerError::Cancelled);
+    }
+    
+    let child = state.server_process.lock().unwrap().take();
+    if let Some(status) = exit_status {
+        // The reader thread may still be draining the last lines
+        std::thread::sleep(STDERR_DRAIN_DELAY);
+        let stderr = kill_and_collect_stderr(child, &state.stderr_tail);
+        log::error!("Server exited during startup ({}). stderr:\n{}", status, stderr.trim_end());
+        if stderr.contains("EADDRINUSE") {
+            return Err(ServerError::PortInUse { port, holder: port_holder(port) });
+        }
+        return Err(ServerError::ServerExited { status: status.to_string(), stderr_tail: stderr });
+    }
+    
+    let stderr = kill_and_collect_stderr(child, &state.stderr_tail);
+    log::error!(
+        "Server did not become ready within {:?}. stderr:\n{}",
+        timeout,
+        stderr.trim_end()
+    );
+    Err(ServerError::StartupTimeout { port, timeout, stderr_tail: stderr })
+}
+
+/// Describes the process listening on `port`, e.g. "node (PID 4321)", as far
+/// as `lsof` (`netstat` on Windows) can tell.
+fn port_holder(port: u16) -> Option<String> {
+    let (command, pid) = port_listeners(port).into_iter().next()?;
+    Some(match command {
+        Some(command) => format!("{} (PID {})", command, pid),
+        None => format!("PID {}", pid),
+    })
+}
+
+/// Whether `pid` is one of the processes listening on `port`. An identity
+/// header is only trusted this far: anything could answer on the port, and
+/// the PID it names is signalled.
+fn holds_port(pid: u32, port: u16) -> bool {
+    port_listeners(port).iter().any(|(_, listener)| *listener == pid)
+}
+
+/// The command names and PIDs listening on `port`, empty if there are none
+/// or `lsof` isn't available.
+#[cfg(unix)]
+fn port_listeners(port: u16) -> Vec<(Option<String>, u32)> {
+    match command("lsof")
+        .args(["-nP", &format!("-iTCP:{}", port), "-sTCP:LISTEN"])
+        .output()
+    {
+        Ok(output) => parse_lsof_listeners(&String::from_utf8_lossy(&output.stdout)),
+        Err(e) => {
+            log::warn!("Failed to run lsof: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(windows)]
+fn port_listeners(port: u16) -> Vec<(Option<String>, u32)> {
+    match command("netstat").args(["-ano", "-p", "TCP"]).output() {
+        Ok(output) => parse_netstat_listeners(&String::from_utf8_lossy(&output.stdout), port),
+        Err(e) => {
+            log::warn!("Failed to run netstat: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn port_listeners(_port: u16) -> Vec<(Option<String>, u32)> {
+    Vec::new()
+}
+
+/// `COMMAND  PID USER   FD   TYPE ... NAME` rows of `lsof` output, after
+/// the header.
+#[cfg(any(unix, test))]
+fn parse_lsof_listeners(output: &str) -> Vec<(Option<String>, u32)> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let command = fields.next()?;
+            Some((Some(command.to_string()), fields.next()?.parse().ok()?))
+        })
+        .collect()
+}
+
+/// `  TCP    127.0.0.1:1234    0.0.0.0:0    LISTENING    4321` rows of
+/// `netstat -ano` output for `port`. netstat doesn't name the command.
+#[cfg(any(windows, test))]
+fn parse_netstat_listeners(output: &str, port: u16) -> Vec<(Option<String>, u32)> {
+    let suffix = format!(":{}", port);
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [_, local, _, "LISTENING", pid] if local.ends_with(&suffix) => Some((None, pid.parse().ok()?)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Reads a millisecond duration from `var`, falling back to `default` when the
+/// variable is unset or not a number.
+fn duration_from_env(var: &str, default: Duration) -> Duration {
+    match std::env::var(var) {
+        Ok(value) => match value.trim().parse::<u64>() {
+            Ok(ms) => Duration::from_millis(ms),
+            Err(_) => {
+                log::warn!("Ignoring invalid {}={:?}, using {:?}", var, value, default);
+                default
+            }
+        },
+        Err(_) => default,
+    }
+}
+
+/// Kills a child that never became ready (if it is still around) and returns
+/// the last lines it wrote to stderr, which are usually the only clue as to
+/// why it failed.
+fn kill_and_collect_stderr(
+    child: Option<ManagedServer>,
+    stderr_tail: &Mutex<VecDeque<String>>,
+) -> String {
+    drop(child);
+    
+    let tail = stderr_tail.lock().unwrap();
+    tail.iter().map(String::as_str).collect::<Vec<_>>().join("\n")
+}
+
+#[tauri::command]
+async fn stop_server(app: tauri::AppHandle) -> Result<(), String> {
+    // A graceful shutdown can wait out the whole grace period
+    run_blocking(app, |_, state| stop_bundled_server(state)).await.map_err(|e| e.to_string())?
+}
+
+fn stop_bundled_server(state: &ServerState) -> Result<(), String> {
+    let child = state
+        .server_process
+        .lock()
+        .map_err(|e| format!("Server state is unavailable: {}", e))?
+        .take();
+    let Some(mut child) = child else {
+        let pid = state.adopted_pid.lock().unwrap().take().ok_or("Server is not running")?;
+        log::info!("Stopping adopted Next.js server with PID: {}", pid);
+        if !terminate_adopted(state, pid) {
+            return Err(format!("Failed to stop server PID {}", pid));
+        }
+        return Ok(());
+    };
+    
+    log::info!("Stopping Next.js server with PID: {}", child.id());
+    let shutdown = terminate_gracefully(&mut child, shutdown_grace_period())
+        .map_err(|e| format!("Failed to stop server: {}", e))?;
+    log::info!("Next.js server {}", shutdown);
+    Ok(())
+}
+
+#[derive(Clone, serde::Serialize)]
+struct ServerInfo {
+    pid: u32,
+    port: u16,
+}
+
+/// Payload of the `startup-progress` events a splash screen can follow
+/// while the server starts: `server_dir_found`, `node_found`, `spawned`,
+/// `waiting` and `ready`, in that order. Restarts, crash restarts included,
+/// emit them again.
+#[derive(Debug, Clone, serde::Serialize)]
+struct StartupProgress {
+    stage: String,
+    detail: Option<String>,
+}
+
+fn emit_progress(app: &tauri::AppHandle, stage: &str, detail: Option<String>) {
+    let progress = StartupProgress { stage: stage.to_string(), detail };
+    show_splash_progress(app, &progress);
+    let _ = app.emit("startup-progress", progress);
+}
+
+/// Hands `progress` to the splash window, if it is still open. The bundled
+/// pages don't load the Tauri JS API, so it can't listen for the event.
+fn show_splash_progress(app: &tauri::AppHandle, progress: &StartupProgress) {
+    let Some(splash) = app.get_webview_window(SPLASH_WINDOW) else { return };
+    let script = match serde_json::to_string(progress) {
+        Ok(json) => format!("window.showProgress && window.showProgress({})", json),
+        Err(e) => {
+            log::warn!("Failed to serialize startup progress: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = splash.eval(&script) {
+        log::warn!("Failed to update the splash window: {}", e);
+    }
+}
+
+/// Clears the restart-in-progress flag however a restart returns.
+struct RestartGuard<'a>(&'a AtomicBool);
+
+impl Drop for RestartGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Replaces the running server with a fresh one. Emits `server-restarting`
+/// up front and then `server-restarted` (with the new PID and port) or
+/// `server-restart-failed`, so the frontend can show a reconnecting overlay
+/// and reload once the server is back. A second call while a restart is in
+/// flight is rejected.
+#[tauri::command]
+async fn restart_server(app: tauri::AppHandle) -> Result<ServerInfo, ServerError> {
+    run_blocking(app, restart_bundled_server)
+        .await
+        .map_err(|e| ServerError::Task(e.to_string()))?
+}
+
+/// Runs `work` on the async runtime's blocking pool. The async commands
+/// spawn, sleep between retries and probe the server there, which would
+/// otherwise tie up one of the few threads every async task runs on.
+async fn run_blocking<T: Send + 'static>(
+    app: tauri::AppHandle,
+    work: impl FnOnce(&tauri::AppHandle, &ServerState) -> T + Send + 'static,
+) -> tauri::Result<T> {
+    tauri::async_runtime::spawn_blocking(move || work(&app, &app.state::<ServerState>())).await
+}
+
+/// `restart_server` for callers already on a thread that may block, like
+/// the health monitor.
+fn restart_bundled_server(app: &tauri::AppHandle, state: &ServerState) -> Result<ServerInfo, ServerError> {
+    if state
+        .restarting
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Err(ServerError::RestartInProgress);
+    }
+    let _guard = RestartGuard(&state.restarting);
+    
+    let _ = app.emit("server-restarting", ());
+    let result = respawn_server(app, state);
+    match &result {
+        Ok(info) => {
+            let _ = app.emit("server-restarted", info.clone());
+        }
+        Err(e) => {
+            log::error!("Server restart failed: {}", e);
+            let _ = app.emit("server-restart-failed", e);
+        }
+    }
+    result
+}
+
+fn respawn_server(app: &tauri::AppHandle, state: &ServerState) -> Result<ServerInfo, ServerError> {
+    kill_server(state);
+    
+    let server_dir = find_server_dir(app)?;
+    // Prefer the current port so the webview URL stays valid, but move if
+    // something else grabbed it while the server was down
+    let previous_port = *state.port.lock().unwrap();
+    let preferred_port = previous_port.unwrap_or_else(|| configured_port(app));
+    let (pid, port) = launch_server(app, state, server_dir, preferred_port, true, &server_env(app))?;
+    let _ = app.emit("server-ready", port);
+    if previous_port != Some(port) {
+        navigate_to_server(app, port)?;
+    }
+    Ok(ServerInfo { pid, port })
+}
+
+#[derive(serde::Serialize)]
+struct ServerHealth {
+    running: bool,
+    pid: Option<u32>,
+    port: Option<u16>,
+    responsive: bool,
+}
+
+#[tauri::command]
+async fn server_health(app: tauri::AppHandle) -> Result<ServerHealth, String> {
+    run_blocking(app, |_, state| {
+        let (running, pid) = state.child_status();
+        let port = *state.port.lock().unwrap();
+        // Ping outside the locks; it can take up to READY_PROBE_TIMEOUT
+        let scheme = state.scheme();
+        let responsive = running && port.is_some_and(|port| server_responds(port, &scheme));
+        ServerHealth { running, pid, port, responsive }
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[derive(serde::Serialize)]
+struct ServerStatus {
+    running: bool,
+    pid: Option<u32>,
+    port: Option<u16>,
+    /// Milliseconds since the Unix epoch
+    started_at: Option<u64>,
+    uptime_secs: Option<u64>,
+    restart_count: u32,
+    node: Option<NodeRuntime>,
+    startup: Option<StartupTiming>,
+    /// Where the server output is saved, same as `server_log_path`
+    log_path: Option<PathBuf>,
+    /// Passed to the server as `OLLY_DATA_DIR` and `OLLY_CACHE_DIR`
+    data_dir: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    stats: Option<ProcessStats>,
+}
+
+#[tauri::command]
+async fn server_status(app: tauri::AppHandle) -> Result<ServerStatus, String> {
+    // Checking the process and sampling its stats can each run a command
+    run_blocking(app, |_, state| collect_server_status(state)).await.map_err(|e| e.to_string())
+}
+
+fn collect_server_status(state: &ServerState) -> ServerStatus {
+    let (running, pid) = state.child_status();
+    let started = *state.started_at.lock().unwrap();
+    let started = started.filter(|_| running);
+    
+    ServerStatus {
+        running,
+        pid,
+        port: *state.port.lock().unwrap(),
+        started_at: started
+            .and_then(|(at, _)| at.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_millis() as u64),
+        uptime_secs: started.map(|(_, at)| at.elapsed().as_secs()),
+        restart_count: state.restart_count.load(Ordering::SeqCst),
+        node: state.node.lock().unwrap().clone(),
+        startup: (*state.startup.lock().unwrap()).filter(|_| running),
+        log_path: state.server_log.lock().unwrap().as_ref().map(|log| log.path().to_path_buf()),
+        data_dir: state.data_dir.lock().unwrap().clone(),
+        cache_dir: state.cache_dir.lock().unwrap().clone(),
+        stats: server_stats(state),
+    }
+}
+
+/// Memory and CPU use of the bundled server right now, to tell its share
+/// apart from the webview's. `None` while no server is running.
+#[tauri::command]
+async fn get_server_stats(app: tauri::AppHandle) -> Result<Option<ProcessStats>, String> {
+    // Sampling runs `ps` on macOS
+    run_blocking(app, |_, state| server_stats(state)).await.map_err(|e| e.to_string())
+}
+
+fn server_stats(state: &ServerState) -> Option<ProcessStats> {
+    let (running, pid) = state.child_status();
+    let pid = pid.filter(|_| running)?;
+    state.stats.lock().unwrap().sample(pid)
+}
+
+/// Starts emitting `server-stats` with what `get_server_stats` returns every
+/// `interval_ms` (at least `MIN_STATS_INTERVAL`), for charting. Each call
+/// replaces the previous stream; 0 just stops it. Nothing is emitted while
+/// no server is running.
+#[tauri::command]
+fn watch_server_stats(app: tauri::AppHandle, state: tauri::State<ServerState>, interval_ms: u64) -> Result<(), String> {
+    let stream = state.stats_stream.fetch_add(1, Ordering::SeqCst) + 1;
+    if interval_ms == 0 {
+        return Ok(());
+    }
+    let interval = Duration::from_millis(interval_ms).max(MIN_STATS_INTERVAL);
+    std::thread::Builder::new()
+        .name("server-stats".to_string())
+        .spawn(move || {
+            let state = app.state::<ServerState>();
+            let current = || state.stats_stream.load(Ordering::SeqCst) == stream;
+            while current() && !state.shutting_down.load(Ordering::SeqCst) {
+                std::thread::sleep(interval);
+                if let Some(stats) = server_stats(&state).filter(|_| current()) {
+                    let _ = app.emit("server-stats", stats);
+                }
+            }
+        })
+        .map(drop)
+        .map_err(|e| format!("Failed to start the stats stream: {}", e))
+}
+
+/// The last `limit` lines the server printed, oldest first, for the in-app
+/// console and bug reports. Served from memory, so polling it is cheap.
+#[tauri::command]
+fn recent_server_logs(state: tauri::State<ServerState>, limit: usize) -> Vec<String> {
+    let server_logs = state.server_logs.lock().unwrap();
+    server_logs.recent(limit).map(|line| line.line.clone()).collect()
+}
+
+/// Like `recent_server_logs`, with each line's timestamp, stream and server PID.
+#[tauri::command]
+fn get_recent_logs(state: tauri::State<ServerState>, limit: usize) -> Vec<LogLine> {
+    state.server_logs.lock().unwrap().recent(limit).cloned().collect()
+}
+
+/// Where this run's server output is saved, for attaching to bug reports.
+/// `None` if the log file couldn't be created.
+#[tauri::command]
+fn server_log_path(state: tauri::State<ServerState>) -> Option<PathBuf> {
+    let server_log = state.server_log.lock().unwrap();
+    server_log.as_ref().map(|server_log| server_log.path().to_path_buf())
+}
+
+/// Starts another Next.js server from `dir` under `name`, for apps that need
+/// more than the bundled one, and returns its PID and port once it answers.
+/// It gets its own port, `servers/<name>` under the data and cache dirs,
+/// and the same `server-env.json`, but no HTTPS.
+#[tauri::command]
+async fn start_named_server(app: tauri::AppHandle, name: String, dir: PathBuf) -> Result<ServerInfo, ServerError> {
+    run_blocking(app, move |app, state| launch_named_server(app, state, &name, dir))
+        .await
+        .map_err(|e| ServerError::Task(e.to_string()))?
+}
+
+fn launch_named_server(
+    app: &tauri::AppHandle,
+    state: &ServerState,
+    name: &str,
+    server_dir: PathBuf,
+) -> Result<ServerInfo, ServerError> {
+    if !valid_server_name(name) {
+        return Err(ServerError::InvalidServerName(name.to_string()));
+    }
+    if name == DEFAULT_SERVER_NAME || state.servers.lock().unwrap().contains_key(name) {
+        return Err(ServerError::ServerNameInUse(name.to_string()));
+    }
+    log::info!("Starting Next.js server {:?} from: {:?}", name, server_dir);
+    let Some(strategy) = launch_strategy(&server_dir) else {
+        return Err(ServerError::ServerJsMissing(server_dir.join("server.js")));
+    };
+    let node = find_node_binary(&server_dir, min_node_version())?;
+    
+    let instance_dir = |kind: &str, root: &Mutex<Option<PathBuf>>| {
+        let root = root.lock().unwrap().clone()?;
+        ensure_app_dir(&format!("{} {}", name, kind), Ok(root.join("servers").join(name)))
+    };
+    let data_dir = instance_dir("data", &state.data_dir);
+    let cache_dir = instance_dir("cache", &state.cache_dir);
+    let extra_env = server_env(app);
+    let interval = duration_from_env("OLLY_READY_POLL_INTERVAL_MS", DEFAULT_READY_POLL_INTERVAL);
+    let host = server_hostname();
+    // Same attempts, timeout and budget as the bundled server
+    let (child, port) = retry_startup(&StartupPolicy::load(app), |timeout| {
+        let port = find_free_port(0);
+        let command = server_command(
+            &node.path,
+            &strategy,
+            &server_dir,
+            port,
+            &extra_env,
+            (data_dir.as_deref(), cache_dir.as_deref()),
+            None,
+        );
+        let mut child = spawn_server(command, &extra_env)?;
+        forward_server_output(&mut child, state, name);
+        let mut child = ManagedServer(child);
+        log::info!("Next.js server {:?} started with PID: {} on port {}", name, child.id(), port);
+        
+        let mut exit_status = None;
+        let ready = wait_for_server_ready(connect_host(&host), port, &Scheme::Http, timeout, interval, || false, || {
+            exit_status = child.try_wait().ok().flatten();
+            exit_status.is_some() || state.shutting_down.load(Ordering::SeqCst)
+        });
+        // Dropping the child on the way out stops it
+        if ready.is_none() {
+            let stderr_tail = "see the server log".to_string();
+            return Err(match exit_status {
+                Some(status) => ServerError::ServerExited { status: status.to_string(), stderr_tail },
+                None if state.shutting_down.load(Ordering::SeqCst) => ServerError::Cancelled,
+                None => ServerError::StartupTimeout { port, timeout, stderr_tail },
+            });
+        }
+        Ok((child, port))
+    })?;
+    let pid = child.id();
+    
+    let mut servers = state.servers.lock().unwrap();
+    if state.shutting_down.load(Ordering::SeqCst) {
+        return Err(ServerError::Cancelled);
+    }
+    if servers.contains_key(name) {
+        // Lost a race with another start of the same name
+        return Err(ServerError::ServerNameInUse(name.to_string()));
+    }
+    log::info!("Next.js server {:?} ready on port {}", name, port);
+    servers.insert(name.to_string(), ServerHandle { child, port, server_dir });
+    Ok(ServerInfo { pid, port })
+}
+
+/// Names end up in directory names, so they are kept to a safe set.
+fn valid_server_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Stops a server started with `start_named_server`. `"default"` stops the
+/// bundled server, like `stop_server`.
+#[tauri::command]
+async fn stop_named_server(app: tauri::AppHandle, name: String) -> Result<(), String> {
+    run_blocking(app, move |_, state| stop_server_named(state, &name)).await.map_err(|e| e.to_string())?
+}
+
+fn stop_server_named(state: &ServerState, name: &str) -> Result<(), String> {
+    if name == DEFAULT_SERVER_NAME {
+        return stop_bundled_server(state);
+    }
+    let mut handle = state
+        .servers
+        .lock()
+        .unwrap()
+        .remove(name)
+        .ok_or_else(|| format!("No server named {:?} is running", name))?;
+    log::info!(
+        "Stopping Next.js server {:?} with PID: {} (port {}, from {:?})",
+        name,
+        handle.child.id(),
+        handle.port,
+        handle.server_dir
+    );
+    let shutdown = terminate_gracefully(&mut handle.child, shutdown_grace_period())
+        .map_err(|e| format!("Failed to stop server {:?}: {}", name, e))?;
+    log::info!("Next.js server {:?} {}", name, shutdown);
+    Ok(())
+}
+
+/// Stops every named server, when the app quits. They get one grace period
+/// between them.
+fn stop_named_servers(state: &ServerState) {
+    let mut servers: Vec<(String, ServerHandle)> = match state.servers.lock() {
+        Ok(mut servers) => servers.drain().collect(),
+        Err(_) => return,
+    };
+    for (name, handle) in &servers {
+        log::info!("Stopping Next.js server {:?} with PID: {}", name, handle.child.id());
+    }
+    let mut children: Vec<&mut Child> = servers.iter_mut().map(|(_, handle)| &mut *handle.child).collect();
+    let shutdowns = terminate_all_gracefully(&mut children, shutdown_grace_period());
+    for ((name, _), shutdown) in servers.iter().zip(shutdowns) {
+        match shutdown {
+            Ok(shutdown) => log::info!("Next.js server {:?} {}", name, shutdown),
+            Err(e) => log::error!("Failed to stop server {:?}: {}", name, e),
+        }
+    }
+}
+
+/// Opens the bundled server directory in Finder/Explorer, to check that the
+/// server files actually made it into the build.
+#[tauri::command]
+fn reveal_server_dir(app: tauri::AppHandle) -> Result<(), String> {
+    let server_dir = find_server_dir(&app).map_err(|e| e.to_string())?;
+    app.opener()
+        .open_path(server_dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| format!("Failed to open {:?}: {}", server_dir, e))
+}
+
+/// Opens the log directory in Finder/Explorer/the file manager: the app's
+/// own log from `tauri_plugin_log` and `next-server.log` (with the previous
+/// runs' rotated copies) both live there. Creates it first if nothing has
+/// been logged yet, so the action never just does nothing.
+#[tauri::command]
+fn open_logs_dir(app: tauri::AppHandle, state: tauri::State<ServerState>) -> Result<(), String> {
+    let dir = log_dir(&app, &state)?;
+    app.opener()
+        .open_path(dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| format!("Failed to open {:?}: {}", dir, e))
+}
+
+/// Like `open_logs_dir`, but selects this run's `next-server.log` in the
+/// file manager once there is one, for a "Reveal logs" button.
+#[tauri::command]
+fn reveal_server_logs(app: tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<ServerState>();
+    let dir = log_dir(&app, &state)?;
+    let server_log = state.server_log.lock().unwrap().as_ref().map(|log| log.path().to_path_buf());
+    match server_log.filter(|path| path.is_file()) {
+        Some(path) => app
+            .opener()
+            .reveal_item_in_dir(&path)
+            .map_err(|e| format!("Failed to reveal {:?}: {}", path, e)),
+        None => app
+            .opener()
+            .open_path(dir.to_string_lossy(), None::<&str>)
+            .map_err(|e| format!("Failed to open {:?}: {}", dir, e)),
+    }
+}
+
+/// The directory `next-server.log` is written to, which is the app log
+/// dir, made sure to exist.
+fn log_dir(app: &tauri::AppHandle, state: &ServerState) -> Result<PathBuf, String> {
+    let server_log_dir = state
+        .server_log
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|log| log.path().parent().map(Path::to_path_buf));
+    let dir = match server_log_dir {
+        Some(dir) => dir,
+        None => app.path().app_log_dir().map_err(|e| format!("No log directory: {}", e))?,
+    };
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    Ok(dir)
+}
+
+/// The platform's default menu with Help > Open Logs Folder added. Menu
+/// events from the tray menu arrive in `handle_menu_event` too.
+fn app_menu(app: &tauri::AppHandle) -> tauri::Result<Menu<tauri::Wry>> {
+    let menu = Menu::default(app)?;
+    if let Some(help) = menu.get(HELP_SUBMENU_ID).and_then(|item| item.as_submenu().cloned()) {
+        help.append(&MenuItem::with_id(app, OPEN_LOGS_MENU_ID, "Open Logs Folder", true, None::<&str>)?)?;
+    }
+    Ok(menu)
+}
+
+fn handle_menu_event(app: &tauri::AppHandle, event: MenuEvent) {
+    if event.id().as_ref() == OPEN_LOGS_MENU_ID {
+        if let Err(e) = open_logs_dir(app.clone(), app.state::<ServerState>()) {
+            log::error!("{}", e);
+            app.dialog()
+                .message(e)
+                .title("Could not open the logs folder")
+                .kind(MessageDialogKind::Error)
+                .show(|_| {});
+        }
+    }
+}
+
+/// Origin of the server the frontend should talk to: the dev server in debug
+/// builds, the bundled one (once it has a port) otherwise. Pages served by the
+/// server can use their own origin, and bundled pages get it as their
+/// `server_url` parameter; this is for other windows.
+#[tauri::command]
+fn get_server_url(state: tauri::State<ServerState>) -> Option<String> {
+    current_server_url(&state)
+}
+
+fn current_server_url(state: &ServerState) -> Option<String> {
+    if cfg!(debug_assertions) {
+        return dev_server_url().map(|url| url.to_string());
+    }
+    let https = state.https.load(Ordering::SeqCst);
+    state.port.lock().unwrap().map(|port| server_url(port, https))
+}
+
+#[tauri::command]
+fn server_port(state: tauri::State<ServerState>) -> Option<u16> {
+    *state.port.lock().unwrap()
+}
+
+/// PID of the running server (or of the leftover one we took over), for
+/// diagnostics users can look up in Activity Monitor or Task Manager. `None`
+/// while no server is running, including after it exited.
+#[tauri::command]
+fn server_pid(state: tauri::State<ServerState>) -> Option<u32> {
+    match state.child_status() {
+        (true, pid) => pid,
+        (false, _) => None,
+    }
+}
+
+/// How a stopped server went down.
+enum Shutdown {
+    /// Exited by itself after being asked to, or had already exited.
+    Graceful(ExitStatus),
+    /// Still running when the grace period ran out, so it was killed.
+    Forced(ExitStatus),
+}
+
+impl std::fmt::Display for Shutdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Shutdown::Graceful(status) => write!(f, "exited gracefully ({})", status),
+            Shutdown::Forced(status) => write!(f, "was force-killed ({})", status),
+        }
+    }
+}
+
+/// Grace period from `OLLY_SHUTDOWN_TIMEOUT_MS`, capped at
+/// `MAX_SHUTDOWN_GRACE_PERIOD`.
+fn shutdown_grace_period() -> Duration {
+    duration_from_env("OLLY_SHUTDOWN_TIMEOUT_MS", DEFAULT_SHUTDOWN_GRACE_PERIOD).min(MAX_SHUTDOWN_GRACE_PERIOD)
+}
+
+/// Asks the child to shut down and reaps it, so Next.js can flush its SQLite
+/// writes and close connections. On Unix its process group gets SIGTERM;
+/// Windows gets `taskkill /T` without `/F`, which posts a close request to
+/// the whole tree. Escalates to a hard kill of the tree if the child is still
+/// running after `grace`.
+fn terminate_gracefully(child: &mut Child, grace: Duration) -> std::io::Result<Shutdown> {
+    terminate_all_gracefully(&mut [child], grace).remove(0)
+}
+
+/// `terminate_gracefully` for several children at once: all of them are
+/// asked to shut down first and then share one grace period, so stopping N
+/// servers takes at most `grace` rather than N times that. Results are in
+/// the order of `children`.
+fn terminate_all_gracefully(children: &mut [&mut Child], grace: Duration) -> Vec<std::io::Result<Shutdown>> {
+    // None while a child has been asked to exit and hasn't yet
+    let mut results: Vec<Option<std::io::Result<Shutdown>>> = children
+        .iter_mut()
+        .map(|child| match child.try_wait() {
+            Ok(Some(status)) => Some(Ok(Shutdown::Graceful(status))),
+            Ok(None) if request_shutdown(child) => None,
+            Ok(None) => Some(reap_child(child).map(Shutdown::Forced)),
+            Err(e) => Some(Err(e)),
+        })
+        .collect();
+    
+    let deadline = Instant::now() + grace;
+    while results.iter().any(Option::is_none) && Instant::now() < deadline {
+        for (child, result) in children.iter_mut().zip(&mut results) {
+            if result.is_none() {
+                *result = child.try_wait().map(|status| status.map(Shutdown::Graceful)).transpose();
+            }
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    
+    children
+        .iter_mut()
+        .zip(results)
+        .map(|(child, result)| {
+            result.unwrap_or_else(|| {
+                log::warn!(
+                    "Server PID {} did not exit within {:?} of the shutdown request, killing it",
+                    child.id(),
+                    grace
+                );
+                reap_child(child).map(Shutdown::Forced)
+            })
+        })
+        .collect()
+}
+
+/// Kills the child and the processes it started if it is still running, and
+/// waits for it. A child that is never waited on lingers as a zombie on Unix
+/// until the app exits.
+fn reap_child(child: &mut Child) -> std::io::Result<ExitStatus> {
+    if let Some(status) = child.try_wait()? {
+        return Ok(status);
+    }
+    kill_tree(child);
+    // Usually already dead from kill_tree; this covers a child outside its
+    // own group (or a failed taskkill)
+    if let Err(e) = child.kill() {
+        if child.try_wait()?.is_none() {
+            return Err(e);
+        }
+    }
+    child.wait()
+}
+
+/// Hard-kills the child's process group, which `start_next_server` makes
+/// it the leader of, so workers Next.js spawned (sharp, build workers) don't
+/// outlive it holding file locks.
+#[cfg(unix)]
+fn kill_tree(child: &Child) {
+    // SAFETY: kill(2) has no memory-safety preconditions. The child isn't
+    // reaped yet, so its PID (and thus the group ID) can't have been recycled.
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+/// Hard-kills the child and its descendants with `taskkill /T /F`.
+#[cfg(windows)]
+fn kill_tree(child: &Child) {
+    if let Err(e) = command("taskkill").args(["/PID", &child.id().to_string(), "/T", "/F"]).output() {
+        log::warn!("Failed to run taskkill: {}", e);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn kill_tree(_child: &Child) {}
+
+/// The server process as held in `ServerState`. Dropping it kills and reaps
+/// the process, so a missed shutdown path or an unwinding panic can't leak
+/// it. Stop it with `terminate_gracefully` first to let Next.js exit cleanly;
+/// the drop is then a no-op.
+struct ManagedServer(Child);
+
+impl std::ops::Deref for ManagedServer {
+    type Target = Child;
+    
+    fn deref(&self) -> &Child {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for ManagedServer {
+    fn deref_mut(&mut self) -> &mut Child {
+        &mut self.0
+    }
+}
+
+impl Drop for ManagedServer {
+    fn drop(&mut self) {
+        if let Err(e) = reap_child(&mut self.0) {
+            log::error!("Failed to reap server PID {}: {}", self.0.id(), e);
+        }
+    }
+}
+
+/// Sends the platform's polite shutdown request. Returns false if it could
+/// not be delivered, in which case there is no point waiting.
+#[cfg(unix)]
+fn request_shutdown(child: &Child) -> bool {
+    let pid = child.id() as libc::pid_t;
+    // SAFETY: kill(2) has no memory-safety preconditions; the PID belongs to
+    // a child we haven't reaped yet, so it can't have been recycled. The
+    // whole group gets it, so workers can shut down cleanly too.
+    unsafe { libc::kill(-pid, libc::SIGTERM) == 0 || libc::kill(pid, libc::SIGTERM) == 0 }
+}
+
+#[cfg(windows)]
+fn request_shutdown(child: &Child) -> bool {
+    // Console processes without a window often refuse this, and taskkill then
+    // exits non-zero; the caller falls through to a hard kill
+    match command("taskkill").args(["/PID", &child.id().to_string(), "/T"]).output() {
+        Ok(output) => output.status.success(),
+        Err(e) => {
+            log::warn!("Failed to run taskkill: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn request_shutdown(_child: &Child) -> bool {
+    false
+}
+
+fn kill_server(state: &ServerState) {
+    if let Ok(mut server) = state.server_process.lock() {
+        if let Some(mut child) = server.take() {
+            log::info!("Stopping Next.js server with PID: {}", child.id());
+            match terminate_gracefully(&mut child, shutdown_grace_period()) {
+                Ok(shutdown) => log::info!("Next.js server {}", shutdown),
+                Err(e) => log::error!("Failed to stop server: {}", e),
+            }
+        }
+    }
+    if let Some(pid) = state.adopted_pid.lock().ok().and_then(|mut adopted| adopted.take()) {
+        log::info!("Stopping adopted Next.js server with PID: {}", pid);
+        if !terminate_adopted(state, pid) {
+            log::error!("Failed to stop adopted server PID {}", pid);
+        }
+    }
+}
+
+/// Stops an adopted server if it still holds the server port. If it
+/// doesn't, it has exited already and the PID may belong to something else
+/// now, so there's nothing left to stop.
+fn terminate_adopted(state: &ServerState, pid: u32) -> bool {
+    let port = *state.port.lock().unwrap();
+    if !port.is_some_and(|port| holds_port(pid, port)) {
+        log::warn!("Adopted server PID {} no longer holds port {:?}, not signalling it", pid, port);
+        return true;
+    }
+    terminate_pid(pid, shutdown_grace_period())
+}
+
+/// Looks for one of our servers on `port` left running by a previous run
+/// that crashed before it could stop it, and applies `OLLY_LEFTOVER_SERVER`:
+/// adopt it or stop it so a fresh one can take the port. Anything else on
+/// the port is left alone and the server starts on a free port instead.
+/// Returns true if the leftover was adopted and nothing needs spawning.
+fn handle_leftover_server(state: &ServerState, port: u16) -> bool {
+    if port == 0 {
+        return false;
+    }
+    let hostname = server_hostname();
+    let Some(addr) = (connect_host(&hostname), port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.find(|addr| TcpStream::connect_timeout(addr, READY_PROBE_TIMEOUT).is_ok()))
+    else {
+        return false;
+    };
+    if state.tls.lock().unwrap().is_some() {
+        log::warn!("Port {} is in use and can't be identified over HTTPS, using a free port", port);
+        return false;
+    }
+    let Some(pid) = identify_server(&addr, &under_base_path(IDENTITY_PATH)) else {
+        log::warn!(
+            "Port {} is held by another program ({}), using a free port",
+            port,
+            port_holder(port).unwrap_or_else(|| "unknown process".to_string())
+        );
+        return false;
+    };
+    // Adopting counts too: kill_server stops the adopted PID on quit
+    if !holds_port(pid, port) {
+        log::warn!(
+            "Port {} answered as our server with PID {}, but that PID doesn't hold the port ({}), using a free port",
+            port,
+            pid,
+            port_holder(port).unwrap_or_else(|| "unknown process".to_string())
+        );
+        return false;
+    }
+    
+    match LeftoverPolicy::from_env() {
+        LeftoverPolicy::Adopt => {
+            log::info!("Adopting the server a previous run left on port {} (PID {})", port, pid);
+            *state.adopted_pid.lock().unwrap() = Some(pid);
+            *state.port.lock().unwrap() = Some(port);
+            state.https.store(false, Ordering::SeqCst);
+            state.record_start(false);
+            true
+        }
+        LeftoverPolicy::Replace => {
+            log::info!("Stopping the server a previous run left on port {} (PID {})", port, pid);
+            if !terminate_pid(pid, shutdown_grace_period()) {
+                log::warn!("Leftover server PID {} is still running, using a free port", pid);
+            }
+            false
+        }
+    }
+}
+
+/// Creates one of the app's own directories if needed. `None` (and a
+/// warning) if it can't be resolved or created; the server then falls back to
+/// its working directory.
+fn ensure_app_dir(kind: &str, dir: tauri::Result<PathBuf>) -> Option<PathBuf> {
+    let dir = match dir {
+        Ok(dir) => dir,
+        Err(e) => {
+            log::warn!("No app {} directory: {}", kind, e);
+            return None;
+        }
+    };
+    match std::fs::create_dir_all(&dir) {
+        Ok(()) => {
+            log::info!("App {} directory: {:?}", kind, dir);
+            Some(dir)
+        }
+        Err(e) => {
+            log::warn!("Failed to create the app {} directory {:?}: {}", kind, dir, e);
+            None
+        }
+    }
 }
 
-fn find_server_dir(app: &tauri::App) -> Option<PathBuf> {
-    // Try resource_dir first (production)
-    if let Ok(resource_dir) = app.path().resource_dir() {
-        let server_dir = resource_dir.join("server");
-        if server_dir.exists() {
-            log::info!("Found server in resource_dir: {:?}", server_dir);
-            return Some(server_dir);
+/// The certificate to serve with if `OLLY_HTTPS` asks for HTTPS, kept in the
+/// app data dir. Without one the server falls back to HTTP.
+fn https_files(app: &tauri::AppHandle) -> Option<TlsFiles> {
+    if !https_requested() {
+        return None;
+    }
+    let dir = match app.path().app_data_dir() {
+        Ok(dir) => dir.join("tls"),
+        Err(e) => {
+            log::warn!("No app data directory for the HTTPS certificate, serving over HTTP: {}", e);
+            return None;
+        }
+    };
+    match ensure_self_signed(&dir) {
+        Ok(files) => Some(files),
+        Err(e) => {
+            log::warn!("Could not set up a certificate in {:?}, serving over HTTP: {}", dir, e);
+            None
         }
     }
+}
+
+/// Finds, spawns and waits for the bundled Next.js server, then points the
+/// main window at it. Runs on a background thread; the error is a
+/// user-facing explanation of what went wrong.
+fn start_bundled_server(app: &tauri::AppHandle) -> Result<(), ServerError> {
+    let server_dir = find_server_dir(app)?;
     
-    // Try executable path (alternative production location)
-    if let Ok(exe_path) = std::env::current_exe() {
-        if let Some(app_dir) = exe_path.parent() {
-            // macOS: Contents/MacOS -> Contents/Resources
-            let resources_dir = app_dir.parent().map(|p| p.join("Resources"));
-            if let Some(res_dir) = resources_dir {
-                let server_dir = res_dir.join("server");
-                if server_dir.exists() {
-                    log::info!("Found server via exe path: {:?}", server_dir);
-                    return Some(server_dir);
-                }
-            }
+    let _ = app.emit("server-starting", ());
+    let state = app.state::<ServerState>();
+    *state.tls.lock().unwrap() = https_files(app);
+    let preferred_port = configured_port(app);
+    let port = if handle_leftover_server(&state, preferred_port) {
+        // Not our child, so there is no output to follow or crash to notice
+        *state.server_dir.lock().unwrap() = Some(server_dir);
+        emit_progress(app, "ready", Some(server_url(preferred_port, false)));
+        preferred_port
+    } else {
+        launch_server(app, &state, server_dir, preferred_port, false, &server_env(app))?.1
+    };
+    let _ = app.emit("server-ready", port);
+    
+    navigate_to_server(app, port)?;
+    show_main_window(app);
+    
+    spawn_crash_monitor(app.clone());
+    spawn_health_monitor(app.clone());
+    Ok(())
+}
+
+/// The main window starts hidden (see tauri.conf.json) so users don't stare
+/// at a connection error before the loading page is up. Every startup outcome
+/// has to end up here, or the app is left running without a window. Closes
+/// the splash window, its job is done.
+fn show_main_window(app: &tauri::AppHandle) {
+    let Some(window) = app.get_webview_window("main") else { return };
+    if let Err(e) = window.show().and_then(|()| window.set_focus()) {
+        log::error!("Failed to show the main window: {}", e);
+    }
+    if let Some(splash) = app.get_webview_window(SPLASH_WINDOW) {
+        if let Err(e) = splash.close() {
+            log::error!("Failed to close the splash window: {}", e);
         }
     }
-    
-    log::error!("Could not find server directory");
-    None
 }
 
-fn find_node_binary() -> Option<PathBuf> {
-    // Try common Node.js locations on macOS
-    let possible_paths = [
-        "/usr/local/bin/node",
-        "/opt/homebrew/bin/node",
-        "/usr/bin/node",
-    ];
-    
-    for path in possible_paths {
-        let node_path = PathBuf::from(path);
-        if node_path.exists() {
-            log::info!("Found Node.js at: {:?}", node_path);
-            return Some(node_path);
+/// Opens a small window with `pages/splash.html`, which shows the
+/// `startup-progress` stages while the main window stays hidden. Returns
+/// false if it couldn't be opened.
+fn open_splash_window(app: &tauri::AppHandle) -> bool {
+    let splash = tauri::WebviewWindowBuilder::new(app, SPLASH_WINDOW, tauri::WebviewUrl::App("splash.html".into()))
+        .title("Olly Molly")
+        .inner_size(360.0, 220.0)
+        .resizable(false)
+        .decorations(false)
+        .center()
+        .build();
+    match splash {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!("Failed to open the splash window: {}", e);
+            false
         }
     }
-    
-    // Try to find node in PATH using 'which'
-    if let Ok(output) = Command::new("which").arg("node").output() {
-        if output.status.success() {
-            let path_str = String::from_utf8_lossy(&output.stdout);
-            let path = PathBuf::from(path_str.trim());
-            if path.exists() {
-                log::info!("Found Node.js via which: {:?}", path);
-                return Some(path);
+}
+
+/// Payload of the `second-instance` event: the command line of a launch
+/// that was blocked because the app is already running, so files and deep
+/// links it was opened with reach this instance.
+#[derive(Debug, Clone, serde::Serialize)]
+struct SecondInstance {
+    /// Including the executable, as in `std::env::args`
+    args: Vec<String>,
+    /// The blocked launch's working directory, to resolve relative paths in
+    /// `args`
+    cwd: String,
+}
+
+/// Brings the existing window to the front when the app is launched again.
+/// If it isn't shown yet, it will be once setup gets that far; the splash
+/// window, if open, is focused meanwhile.
+fn focus_main_window(app: &tauri::AppHandle) {
+    let Some(window) = app.get_webview_window("main") else { return };
+    if !window.is_visible().unwrap_or(false) {
+        log::info!("Main window isn't shown yet");
+        if let Some(splash) = app.get_webview_window(SPLASH_WINDOW) {
+            let _ = splash.set_focus();
+        }
+        return;
+    }
+    // Focusing alone leaves a minimized window in the dock/taskbar
+    if window.is_minimized().unwrap_or(false) {
+        if let Err(e) = window.unminimize() {
+            log::error!("Failed to unminimize the main window: {}", e);
+        }
+    }
+    if let Err(e) = window.set_focus() {
+        log::error!("Failed to focus the main window: {}", e);
+    }
+}
+
+/// URL of a page bundled from `pages/`, at the origin Tauri serves the
+/// bundled frontend from on each platform. Once the server has an address
+/// the page also gets it as `server_url`, what `get_server_url` returns.
+fn bundled_page_url<'a>(
+    state: &ServerState,
+    page: &str,
+    params: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<tauri::Url, impl std::fmt::Display> {
+    let origin = if cfg!(windows) { "http://tauri.localhost" } else { "tauri://localhost" };
+    tauri::Url::parse_with_params(&format!("{}/{}", origin, page), params).map(|mut url| {
+        if let Some(server_url) = current_server_url(state) {
+            url.query_pairs_mut().append_pair("server_url", &server_url);
+        }
+        url
+    })
+}
+
+/// Loads the bundled `pages/error.html` with `message`, in place of the dead
+/// server URL.
+fn show_error_page(app: &tauri::AppHandle, message: &str) {
+    let Some(window) = app.get_webview_window("main") else { return };
+    match bundled_page_url(&app.state::<ServerState>(), "error.html", [("message", message)]) {
+        Ok(url) => {
+            if let Err(e) = window.navigate(url) {
+                log::error!("Failed to load the error page: {}", e);
             }
         }
+        Err(e) => log::error!("Invalid error page URL: {}", e),
     }
-    
-    log::error!("Could not find Node.js binary");
-    None
+    show_main_window(app);
 }
 
-fn start_next_server(server_dir: PathBuf) -> Option<Child> {
-    let server_js = server_dir.join("server.js");
-    
-    log::info!("Starting Next.js server from: {:?}", server_dir);
+/// Spawns the server from `server_dir` and waits until it answers, returning
+/// its PID and the port it ended up on: the one it printed in its `- Local:`
+/// line if it did, since Node may not have bound the one it was given.
+/// Starts that time out or die are retried as `StartupPolicy` allows.
+fn launch_server(
+    app: &tauri::AppHandle,
+    state: &ServerState,
+    server_dir: PathBuf,
+    preferred_port: u16,
+    is_restart: bool,
+    extra_env: &HashMap<String, String>,
+) -> Result<(u32, u16), ServerError> {
+    emit_progress(app, "server_dir_found", Some(server_dir.display().to_string()));
+    *state.server_dir.lock().unwrap() = Some(server_dir.clone());
+    let policy = StartupPolicy::load(app);
+    let (launched, _) = launch_on_free_port(preferred_port, |port| {
+        retry_startup(&policy, |timeout| {
+            *state.port.lock().unwrap() = Some(port);
+            let child = start_next_server(app, state, server_dir.clone(), port, extra_env)?;
+            let pid = child.id();
+            state.adopt_child(child, is_restart)?;
+            emit_progress(app, "waiting", Some(format!("port {}", port)));
+            await_server_ready(state, port, timeout)?;
+            // What the server printed beats what we asked for
+            let listening = state.port.lock().unwrap().unwrap_or(port);
+            emit_progress(app, "ready", Some(server_url(listening, state.https.load(Ordering::SeqCst))));
+            Ok((pid, listening))
+        })
+    })?;
+    Ok(launched)
+}
+
+/// Runs `launch` on a free port, preferring `preferred`. Another process can
+/// still grab the port between the check and Node binding it, so on
+/// EADDRINUSE a fresh port is picked and the launch retried, up to
+/// `PORT_ATTEMPTS` times. Returns the launch result and the port it used.
+fn launch_on_free_port<T>(
+    preferred: u16,
+    mut launch: impl FnMut(u16) -> Result<T, ServerError>,
+) -> Result<(T, u16), ServerError> {
+    let mut port = find_free_port(preferred);
+    let mut attempt = 1;
+    loop {
+        match launch(port) {
+            Err(ServerError::PortInUse { .. }) if attempt < PORT_ATTEMPTS => {
+                let next = find_free_port(0);
+                log::warn!("Port {} is already in use, retrying on port {}", port, next);
+                port = next;
+                attempt += 1;
+            }
+            result => return result.map(|value| (value, port)),
+        }
+    }
+}
+
+/// The dev server debug builds load: `OLLY_DEV_URL` if set (e.g.
+/// `http://localhost:3000`, to use another port), otherwise `DEFAULT_PORT`
+/// on localhost, where `npm run dev` serves.
+fn dev_server_url() -> Option<tauri::Url> {
+    if let Ok(dev_url) = std::env::var("OLLY_DEV_URL") {
+        match dev_url.parse() {
+            Ok(url) => return Some(url),
+            Err(e) => log::warn!("Ignoring invalid OLLY_DEV_URL={:?}: {}", dev_url, e),
+        }
+    }
+    format!("http://localhost:{}", DEFAULT_PORT).parse().ok()
+}
+
+/// Warns, from a background thread, if nothing answers at the dev server
+/// URL within `DEV_SERVER_TIMEOUT`. The window stays blank until it does,
+/// which is easy to mistake for a broken build. Never fails the app: the dev
+/// server may just be slow to compile the health route.
+fn check_dev_server(url: &tauri::Url) {
+    let Some(host) = url.host_str() else { return };
+    // IPv6 hosts come bracketed
+    let host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+    let Some(port) = url.port_or_known_default() else { return };
+    // The dev server's certificate isn't ours to check
+    let scheme = if url.scheme() == "https" { Scheme::Https(None) } else { Scheme::Http };
+    let url = url.to_string();
+    let spawned = std::thread::Builder::new().name("dev-server-check".to_string()).spawn(move || {
+        let ready = wait_for_server_ready(
+            connect_host(&host),
+            port,
+            &scheme,
+            DEV_SERVER_TIMEOUT,
+            DEFAULT_READY_POLL_INTERVAL,
+            || false,
+            || false,
+        );
+        match ready {
+            Some(_) => log::info!("Dev server at {} is up", url),
+            None => log::warn!(
+                "Dev server at {} is not answering after {:?}. Is `npm run dev` running? \
+                 (set OLLY_DEV_URL if it runs elsewhere)",
+                url,
+                DEV_SERVER_TIMEOUT
+            ),
+        }
+    });
+    if let Err(e) = spawned {
+        log::warn!("Failed to spawn dev server check thread: {}", e);
+    }
+}
+
+/// Points the main window at the server. The window config only has the
+/// bundled loading page, so this is the one place the server's address
+/// reaches the webview.
+fn navigate_to_server(app: &tauri::AppHandle, port: u16) -> Result<(), ServerError> {
+    let Some(window) = app.get_webview_window("main") else {
+        return Ok(());
+    };
+    let https = app.state::<ServerState>().https.load(Ordering::SeqCst);
+    let url = format!("{}{}", server_url(port, https), base_path());
+    log::info!("Opening {}", url);
+    let url = url
+        .parse()
+        .map_err(|e| ServerError::Window(format!("invalid server URL {}: {}", url, e)))?;
+    allow_server_origin(app, &url)?;
+    window
+        .navigate(url)
+        .map_err(|e| ServerError::Window(e.to_string()))
+}
+
+/// What the server's pages may call. Named servers are left out: starting
+/// one runs node from any directory the caller names.
+const SERVER_PAGE_PERMISSIONS: [&str; 16] = [
+    "core:default",
+    "allow-stop-server",
+    "allow-restart-server",
+    "allow-server-port",
+    "allow-server-pid",
+    "allow-get-server-stats",
+    "allow-watch-server-stats",
+    "allow-get-server-url",
+    "allow-server-health",
+    "allow-server-status",
+    "allow-recent-server-logs",
+    "allow-get-recent-logs",
+    "allow-server-log-path",
+    "allow-reveal-server-dir",
+    "allow-open-logs-dir",
+    "allow-reveal-server-logs",
+];
+
+/// Gives pages from the origin of `url`, and only that origin, the
+/// commands in `SERVER_PAGE_PERMISSIONS` in the main window. The server's
+/// port is only known at runtime, so capabilities/default.json just covers
+/// the bundled pages and the configured dev server.
+fn allow_server_origin(app: &tauri::AppHandle, url: &tauri::Url) -> Result<(), ServerError> {
+    let origin = url.origin().ascii_serialization();
+    let capability = SERVER_PAGE_PERMISSIONS.iter().fold(
+        tauri::ipc::CapabilityBuilder::new(format!("server-origin-{}", origin))
+            .remote(origin.clone())
+            .window("main"),
+        |capability, permission| capability.permission(*permission),
+    );
+    app.add_capability(capability)
+        .map_err(|e| ServerError::Window(format!("failed to allow {}: {}", origin, e)))
+}
+
+/// Shows a fatal startup error, both as the window's error page and as a
+/// dialog, and quits once the user dismisses it. When the fix is installing
+/// Node.js the dialog also offers to open the download page. It is
+/// non-blocking so it never stalls the caller. The frontend gets the
+/// serialized error as a `server-failed` event, the counterpart of
+/// `server-ready`.
+fn fail_startup(app: &tauri::AppHandle, error: ServerError) {
+    if let ServerError::Cancelled = error {
+        log::info!("Server startup cancelled, the app is quitting");
+        return;
+    }
+    log::error!("Startup failed: {}", error);
+    let _ = app.emit("server-failed", &error);
+    kill_server(&app.state::<ServerState>());
+    show_error_page(app, &error.to_string());
     
-    if !server_js.exists() {
-        log::error!("server.js not found at {:?}", server_js);
-        return None;
+    let title = if error.needs_node_install() {
+        "Node.js is required"
+    } else if error.needs_reinstall() {
+        "Olly Molly needs to be reinstalled"
+    } else {
+        "Olly Molly failed to start"
+    };
+    let dialog = app
+        .dialog()
+        .message(error.to_string())
+        .title(title)
+        .kind(MessageDialogKind::Error);
+    let handle = app.clone();
+    if error.needs_node_install() {
+        dialog
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                "Download Node.js".to_string(),
+                "Quit".to_string(),
+            ))
+            .show(move |download| {
+                if download {
+                    if let Err(e) = handle.opener().open_url(NODE_DOWNLOAD_URL, None::<&str>) {
+                        log::error!("Failed to open {}: {}", NODE_DOWNLOAD_URL, e);
+                    }
+                }
+                handle.exit(1);
+            });
+    } else {
+        dialog.show(move |_| handle.exit(1));
+    }
+}
+
+/// Watches the running server and respawns it whenever it exits on its own;
+/// a server process is never supposed to finish, so even exit code 0 counts
+/// as a crash. Deliberate stops take the child out of `ServerState` first, so
+/// the monitor never sees them.
+///
+/// Restarts back off exponentially. Once more than `MAX_CRASH_RESTARTS`
+/// crashes (or failed respawns) pile up within `CRASH_RESTART_WINDOW` it stops
+/// trying, emits `server-crashed` with the last exit code and
+/// `server-crash-loop` with all of them, and tells the user. It keeps watching
+/// afterwards so a manual `restart_server` is supervised again.
+///
+/// Each crash emits `server-exited` with the exit code, and each respawn
+/// `server-starting` and then `server-ready` once the new server answers.
+fn spawn_crash_monitor(app: tauri::AppHandle) {
+    let spawned = std::thread::Builder::new()
+        .name("server-monitor".to_string())
+        .spawn(move || {
+            let state = app.state::<ServerState>();
+            let mut crashes: VecDeque<(Instant, Option<i32>)> = VecDeque::new();
+            loop {
+                std::thread::sleep(CRASH_CHECK_INTERVAL);
+                
+                // A restart waits for its own child and reports it dying as
+                // ServerExited; taking the child here would hide that
+                if state.restarting.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let Some(status) = take_exited_child(&state) else { continue };
+                log::error!("Next.js server exited unexpectedly ({})", status);
+                let _ = app.emit("server-exited", status.code());
+                crashes.push_back((Instant::now(), status.code()));
+                
+                loop {
+                    crashes.retain(|(at, _)| at.elapsed() < CRASH_RESTART_WINDOW);
+                    if crashes.len() > MAX_CRASH_RESTARTS {
+                        report_crash_loop(&app, &crashes);
+                        break;
+                    }
+                    
+                    let delay = crash_backoff(crashes.len());
+                    log::info!("Restarting Next.js server in {:?} (attempt {})", delay, crashes.len());
+                    std::thread::sleep(delay);
+                    match restart_crashed_server(&app, &state, &server_env(&app)) {
+                        Ok(true) => break,
+                        Ok(false) => {
+                            log::info!("Server was restarted or stopped in the meantime, skipping the crash restart");
+                            break;
+                        }
+                        Err(e) => {
+                            log::error!("Failed to restart server: {}", e);
+                            crashes.push_back((Instant::now(), None));
+                        }
+                    }
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        log::error!("Failed to spawn server monitor thread: {}", e);
+    }
+}
+
+/// Takes the child out of `ServerState` if it has exited. Every way of
+/// stopping the server takes it out before signalling it (and its process
+/// group), so a deliberate stop never shows up here as a crash.
+fn take_exited_child(state: &ServerState) -> Option<ExitStatus> {
+    let mut server = state.server_process.lock().unwrap();
+    let status = server.as_mut()?.try_wait().ok()??;
+    server.take();
+    Some(status)
+}
+
+/// 1s, 2s, 4s, ... capped at `MAX_CRASH_BACKOFF`.
+fn crash_backoff(crash_count: usize) -> Duration {
+    let exponent = crash_count.saturating_sub(1).min(16) as u32;
+    (Duration::from_secs(1) * 2u32.pow(exponent)).min(MAX_CRASH_BACKOFF)
+}
+
+/// Respawns the server the way `launch_server` starts it, on its old port
+/// unless something took it while the server was down, and waits until it
+/// answers. `Ok(false)` if there was nothing to do because a manual restart,
+/// stop or quit got there first. Holds the restart flag throughout, so a
+/// manual restart can't race it.
+fn restart_crashed_server(
+    app: &tauri::AppHandle,
+    state: &ServerState,
+    extra_env: &HashMap<String, String>,
+) -> Result<bool, String> {
+    if state
+        .restarting
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Ok(false);
+    }
+    let _guard = RestartGuard(&state.restarting);
+    // A manual restart may have replaced the server while we were backing off
+    if state.server_process.lock().unwrap().is_some() || state.shutting_down.load(Ordering::SeqCst) {
+        return Ok(false);
     }
     
-    let node_path = find_node_binary()?;
-    log::info!("Using Node.js from: {:?}", node_path);
-    
-    let child = Command::new(&node_path)
-        .arg(&server_js)
-        .current_dir(&server_dir)
-        .env("PORT", "1234")
-        .env("HOSTNAME", "localhost")
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| {
-            log::error!("Failed to start server: {}", e);
-            e
-        })
-        .ok()?;
+    let server_dir = state.server_dir.lock().unwrap().clone().ok_or("Server directory unknown")?;
+    let previous_port = state.port.lock().unwrap().ok_or("Server port unknown")?;
+    let _ = app.emit("server-starting", ());
+    let port = match launch_server(app, state, server_dir, previous_port, true, extra_env) {
+        Ok((_, port)) => port,
+        Err(ServerError::Cancelled) => return Ok(false),
+        Err(e) => return Err(e.to_string()),
+    };
+    log::info!("Restarted Next.js server is ready");
+    let _ = app.emit("server-ready", port);
+    if port != previous_port {
+        // The server itself is back, so this isn't worth another restart
+        if let Err(e) = navigate_to_server(app, port) {
+            log::error!("Failed to point the window at port {}: {}", port, e);
+        }
+    }
+    Ok(true)
+}
+
+fn report_crash_loop(app: &tauri::AppHandle, crashes: &VecDeque<(Instant, Option<i32>)>) {
+    let exit_codes: Vec<Option<i32>> = crashes.iter().map(|(_, code)| *code).collect();
+    log::error!(
+        "Server crashed {} times within {:?}, giving up. Exit codes: {:?}",
+        crashes.len(),
+        CRASH_RESTART_WINDOW,
+        exit_codes
+    );
+    let _ = app.emit("server-crashed", exit_codes.last().copied().flatten());
+    let _ = app.emit("server-crash-loop", &exit_codes);
     
-    log::info!("Next.js server started with PID: {}", child.id());
-    Some(child)
+    app.dialog()
+        .message(format!(
+            "The Olly Molly server keeps crashing ({} times in the last {} seconds) and will not \
+             be restarted automatically. Check the logs, then use Restart Server or relaunch the app.",
+            crashes.len(),
+            CRASH_RESTART_WINDOW.as_secs()
+        ))
+        .title("Olly Molly server stopped")
+        .kind(MessageDialogKind::Error)
+        .show(|_| {});
 }
 
-#[allow(dead_code)]
-fn kill_server(state: &tauri::State<ServerState>) {
-    if let Ok(mut server) = state.server_process.lock() {
-        if let Some(ref mut child) = *server {
-            log::info!("Killing Next.js server with PID: {}", child.id());
-            let _ = child.kill();
+/// Payload of `server-unhealthy` and `server-recovered`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+struct HealthReport {
+    /// Failed probes in a row; for `server-recovered`, how many there were
+    consecutive_failures: u32,
+    /// How long the last probe took, answered or not
+    latency_ms: u64,
+}
+
+#[derive(Debug, PartialEq)]
+enum HealthChange {
+    Unhealthy(HealthReport),
+    Recovered(HealthReport),
+}
+
+/// Counts failed health probes in a row. A single miss isn't reported;
+/// from `UNHEALTHY_AFTER_FAILURES` on, every miss is, and so is the first
+/// answer after that.
+#[derive(Debug, Default)]
+struct HealthTracker {
+    failures: u32,
+}
+
+impl HealthTracker {
+    fn record(&mut self, healthy: bool, latency: Duration) -> Option<HealthChange> {
+        let latency_ms = latency.as_millis() as u64;
+        if healthy {
+            let failures = std::mem::take(&mut self.failures);
+            return (failures >= UNHEALTHY_AFTER_FAILURES)
+                .then_some(HealthChange::Recovered(HealthReport { consecutive_failures: failures, latency_ms }));
+        }
+        self.failures += 1;
+        (self.failures >= UNHEALTHY_AFTER_FAILURES)
+            .then_some(HealthChange::Unhealthy(HealthReport { consecutive_failures: self.failures, latency_ms }))
+    }
+}
+
+/// `OLLY_HEALTH_RESTART_AFTER`: restart the server after this many failed
+/// health probes in a row. Unset or 0 leaves it running.
+fn health_restart_after() -> Option<u32> {
+    let value = std::env::var("OLLY_HEALTH_RESTART_AFTER").ok()?;
+    match value.trim().parse::<u32>() {
+        Ok(0) => None,
+        Ok(limit) => Some(limit),
+        Err(_) => {
+            log::warn!("Ignoring invalid OLLY_HEALTH_RESTART_AFTER={:?}", value);
+            None
         }
     }
 }
 
+/// Probes the server's health route every `OLLY_HEALTH_INTERVAL_MS` (0
+/// turns it off) to catch a server that is running but no longer answers,
+/// which the crash monitor can't see. Emits `server-unhealthy` and
+/// `server-recovered` with a `HealthReport`, and goes through the
+/// `restart_server` path once `health_restart_after` is reached.
+///
+/// Probing pauses while the server is restarting, gone (the crash monitor's
+/// job) or still starting up, so none of those count as failures.
+fn spawn_health_monitor(app: tauri::AppHandle) {
+    let interval = duration_from_env("OLLY_HEALTH_INTERVAL_MS", DEFAULT_HEALTH_INTERVAL);
+    if interval.is_zero() {
+        log::info!("Server health monitoring is off");
+        return;
+    }
+    let restart_after = health_restart_after();
+    // Every attempt records a fresh start, and none may run longer than the
+    // attempt timeout (later ones get less once the budget runs low)
+    let startup_timeout = StartupPolicy::load(&app).attempt_timeout;
+    let spawned = std::thread::Builder::new()
+        .name("server-health".to_string())
+        .spawn(move || {
+            let state = app.state::<ServerState>();
+            let mut tracker = HealthTracker::default();
+            while !state.shutting_down.load(Ordering::SeqCst) {
+                std::thread::sleep(interval);
+                
+                let running = state.server_process.lock().unwrap().is_some()
+                    || state.adopted_pid.lock().unwrap().is_some();
+                let starting = state.ready_line.lock().unwrap().is_none()
+                    && state.started_at.lock().unwrap().is_some_and(|(_, at)| at.elapsed() < startup_timeout);
+                let port = *state.port.lock().unwrap();
+                let Some(port) = port.filter(|_| running && !starting && !state.restarting.load(Ordering::SeqCst))
+                else {
+                    tracker = HealthTracker::default();
+                    continue;
+                };
+                
+                let probe_started = Instant::now();
+                let healthy = server_responds(port, &state.scheme());
+                match tracker.record(healthy, probe_started.elapsed()) {
+                    Some(HealthChange::Unhealthy(report)) => {
+                        log::warn!("Server failed {} health checks in a row", report.consecutive_failures);
+                        let _ = app.emit("server-unhealthy", &report);
+                        if restart_after.is_some_and(|limit| report.consecutive_failures >= limit) {
+                            log::warn!("Restarting the unresponsive server");
+                            tracker = HealthTracker::default();
+                            // Errors are logged and emitted by restart_bundled_server
+                            let _ = restart_bundled_server(&app, &state);
+                        }
+                    }
+                    Some(HealthChange::Recovered(report)) => {
+                        log::info!("Server answers again after {} failed health checks", report.consecutive_failures);
+                        let _ = app.emit("server-recovered", &report);
+                    }
+                    None => {}
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        log::error!("Failed to spawn server health thread: {}", e);
+    }
+}
+
+/// Makes sure a panic anywhere in the app (including a failed `setup`, which
+/// surfaces as a panic from `build`) doesn't leave the Node server running.
+fn kill_server_on_panic(server_process: Arc<Mutex<Option<ManagedServer>>>) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // try_lock: the panic may have happened while the lock was held
+        if let Ok(mut server) = server_process.try_lock() {
+            if let Some(mut child) = server.take() {
+                // Still worth a short SIGTERM so pending SQLite writes land
+                let _ = terminate_gracefully(&mut child, PANIC_SHUTDOWN_GRACE_PERIOD);
+            }
+        }
+        default_hook(info);
+    }));
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let server_process = Arc::new(Mutex::new(None));
+    kill_server_on_panic(Arc::clone(&server_process));
+    
     tauri::Builder::default()
+        // Must come first: a second launch exits here before it gets as far
+        // as starting a competing server
+        .plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+            log::info!("Another launch was blocked, focusing the existing window (args: {:?})", args);
+            focus_main_window(app);
+            let _ = app.emit("second-instance", SecondInstance { args, cwd });
+        }))
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_process::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_opener::init())
         .plugin(
             tauri_plugin_log::Builder::default()
                 .level(log::LevelFilter::Info)
                 .build(),
         )
         .manage(ServerState {
-            server_process: Mutex::new(None),
+            server_process,
+            adopted_pid: Mutex::new(None),
+            port: Arc::new(Mutex::new(None)),
+            server_dir: Mutex::new(None),
+            restarting: AtomicBool::new(false),
+            started_at: Mutex::new(None),
+            restart_count: AtomicU32::new(0),
+            stderr_tail: Arc::new(Mutex::new(VecDeque::new())),
+            ready_line: Arc::new(Mutex::new(None)),
+            startup: Mutex::new(None),
+            server_logs: Arc::new(Mutex::new(LogBuffer::from_env())),
+            server_log: Mutex::new(None),
+            node: Mutex::new(None),
+            tls: Mutex::new(None),
+            https: AtomicBool::new(false),
+            data_dir: Mutex::new(None),
+            cache_dir: Mutex::new(None),
+            shutting_down: AtomicBool::new(false),
+            servers: Mutex::new(HashMap::new()),
+            stats: Mutex::new(StatsSampler::default()),
+            stats_stream: AtomicU64::new(0),
+        })
+        .invoke_handler(tauri::generate_handler![
+            stop_server,
+            restart_server,
+            server_port,
+            server_pid,
+            get_server_stats,
+            watch_server_stats,
+            get_server_url,
+            server_health,
+            server_status,
+            recent_server_logs,
+            get_recent_logs,
+            server_log_path,
+            reveal_server_dir,
+            open_logs_dir,
+            reveal_server_logs,
+            start_named_server,
+            stop_named_server
+        ])
+        .menu(app_menu)
+        .on_menu_event(handle_menu_event)
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Destroyed = event {
+                if window.label() == "main" {
+                    kill_server(&window.state::<ServerState>());
+                    stop_named_servers(&window.state::<ServerState>());
+                }
+            }
         })
         .setup(|app| {
             log::info!("App setup starting...");
             
-            // In production, start the Next.js server
-            #[cfg(not(debug_assertions))]
-            {
+            match app.path().app_log_dir().map_err(|e| e.to_string()) {
+                Ok(dir) => match ServerLog::open(&dir) {
+                    Ok(server_log) => {
+                        log::info!("Writing server output to {:?}", server_log.path());
+                        *app.state::<ServerState>().server_log.lock().unwrap() = Some(Arc::new(server_log));
+                    }
+                    Err(e) => log::warn!("Failed to create the server log in {:?}: {}", dir, e),
+                },
+                Err(e) => log::warn!("No app log directory, not saving server output: {}", e),
+            }
+            
+            let state = app.state::<ServerState>();
+            *state.data_dir.lock().unwrap() = ensure_app_dir("data", app.path().app_data_dir());
+            *state.cache_dir.lock().unwrap() = ensure_app_dir("cache", app.path().app_cache_dir());
+            
+            // In production, start the Next.js server. cfg! rather than #[cfg]
+            // keeps both paths compiled (and lint-checked) in every profile.
+            if cfg!(not(debug_assertions)) {
                 log::info!("Production mode detected, looking for server...");
                 
-                if let Some(server_dir) = find_server_dir(app) {
-                    let state = app.state::<ServerState>();
-                    let mut server = state.server_process.lock().unwrap();
-                    *server = start_next_server(server_dir);
-                    
-                    // Wait for server to start
-                    log::info!("Waiting for server to start...");
-                    std::thread::sleep(std::time::Duration::from_secs(3));
-                    log::info!("Server should be ready now");
-                } else {
-                    log::error!("Server directory not found!");
+                // Waiting for the server takes seconds; do it off the main
+                // thread so the splash window shows up (and stays
+                // responsive) now. The frontend hears back via server-ready /
+                // server-failed. Without a splash, the main window shows
+                // pages/loading.html until then.
+                if !open_splash_window(app.handle()) {
+                    show_main_window(app.handle());
                 }
-            }
-            
-            #[cfg(debug_assertions)]
-            {
+                if let Err(e) = tray::create_tray(app.handle()) {
+                    log::warn!("Failed to create the tray icon: {}", e);
+                }
+                let handle = app.handle().clone();
+                tauri::async_runtime::spawn_blocking(move || {
+                    if let Err(error) = start_bundled_server(&handle) {
+                        fail_startup(&handle, error);
+                    }
+                });
+            } else {
                 log::info!("Debug mode - using external dev server");
+                if let (Some(window), Some(url)) = (app.get_webview_window("main"), dev_server_url()) {
+                    log::info!("Using dev server at {}", url);
+                    check_dev_server(&url);
+                    // An OLLY_DEV_URL other than devUrl counts as remote
+                    allow_server_origin(app.handle(), &url)?;
+                    window.navigate(url)?;
+                }
+                show_main_window(app.handle());
             }
             
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Fires for every way of quitting: window close, Cmd+Q, dock menu
+            if let tauri::RunEvent::ExitRequested { .. } | tauri::RunEvent::Exit = event {
+                let state = app.state::<ServerState>();
+                state.shutting_down.store(true, Ordering::SeqCst);
+                kill_server(&state);
+                stop_named_servers(&state);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn touch(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    
+    #[test]
+    fn ready_line_reports_startup_time() {
+        let pattern = Regex::new(DEFAULT_READY_PATTERN).unwrap();
+        let parse = |line| parse_ready_line(&pattern, line).and_then(|ready| ready.reported);
+        
+        assert_eq!(parse(" ✓ Ready in 843ms"), Some(Duration::from_millis(843)));
+        assert_eq!(parse(" ✓ Ready in 1.2s"), Some(Duration::from_millis(1200)));
+        assert_eq!(parse_ready_line(&pattern, "   - Local:        http://localhost:1234"), None);
+        assert_eq!(parse_ready_line(&pattern, " ✓ Starting..."), None);
+    }
+    
+    #[test]
+    fn custom_ready_pattern_without_time_still_signals() {
+        let pattern = Regex::new("Listening").unwrap();
+        
+        assert_eq!(
+            parse_ready_line(&pattern, "Listening on port 1234"),
+            Some(ReadyLine { reported: None })
+        );
+    }
+    
+    fn some(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+    
+    #[test]
+    fn listening_port_from_startup_output() {
+        assert_eq!(parse_listening_port("   - Local:        http://localhost:1234"), Some(1234));
+        assert_eq!(parse_listening_port("   - Local:        https://127.0.0.1:4321/app"), Some(4321));
+        assert_eq!(parse_listening_port("   - Local:        http://[::1]:5000"), Some(5000));
+        assert_eq!(
+            parse_listening_port("ready - started server on 0.0.0.0:3000, url: http://localhost:3000"),
+            Some(3000)
+        );
+        assert_eq!(parse_listening_port("   - Network:      http://0.0.0.0:1234"), None);
+        assert_eq!(parse_listening_port(" ✓ Ready in 120ms"), None);
+        assert_eq!(parse_listening_port("   - Local:        http://localhost"), None);
+    }
+    
+    #[test]
+    fn health_tracker_ignores_single_miss() {
+        let mut tracker = HealthTracker::default();
+        let latency = Duration::from_millis(5);
+        
+        assert_eq!(tracker.record(false, latency), None);
+        assert_eq!(tracker.record(true, latency), None);
+        assert_eq!(tracker.record(false, latency), None);
+    }
+    
+    #[test]
+    fn health_tracker_reports_unhealthy_and_recovered() {
+        let mut tracker = HealthTracker::default();
+        let report = |consecutive_failures, latency_ms| HealthReport { consecutive_failures, latency_ms };
+        
+        tracker.record(false, Duration::from_millis(2000));
+        assert_eq!(
+            tracker.record(false, Duration::from_millis(2000)),
+            Some(HealthChange::Unhealthy(report(2, 2000)))
+        );
+        assert_eq!(
+            tracker.record(false, Duration::from_millis(2000)),
+            Some(HealthChange::Unhealthy(report(3, 2000)))
+        );
+        assert_eq!(tracker.record(true, Duration::from_millis(12)), Some(HealthChange::Recovered(report(3, 12))));
+        assert_eq!(tracker.record(true, Duration::from_millis(12)), None);
+    }
+    
+    #[test]
+    fn dev_server_scripts_use_default_port() {
+        let package: serde_json::Value = serde_json::from_str(include_str!("../../package.json")).unwrap();
+        for script in ["dev", "start"] {
+            let command = package["scripts"][script].as_str().unwrap();
+            assert!(command.ends_with(&format!("--port {}", DEFAULT_PORT)), "{}: {}", script, command);
+        }
+    }
+    
+    #[test]
+    fn port_sources_in_precedence_order() {
+        let sources = [
+            ("--port", None),
+            ("OLLY_PORT", some("4567")),
+            ("config.json", some("5678")),
+        ];
+        assert_eq!(choose_port(sources), (4567, "OLLY_PORT"));
+        assert_eq!(choose_port([("--port", some("0")), ("OLLY_PORT", some("4567"))]), (0, "--port"));
+        assert_eq!(choose_port([("OLLY_PORT", None)]), (DEFAULT_PORT, "default"));
+    }
+    
+    #[test]
+    fn invalid_port_falls_through_to_next_source() {
+        let sources = [
+            ("--port", some("http")),
+            ("OLLY_PORT", some("80")),
+            ("config.json", some("70000")),
+        ];
+        assert_eq!(choose_port(sources), (DEFAULT_PORT, "default"));
+        let sources = [("--port", some("80")), ("config.json", some(" 8080 "))];
+        assert_eq!(choose_port(sources), (8080, "config.json"));
+    }
+    
+    #[test]
+    fn port_arg_accepts_both_forms() {
+        let args = |args: &[&str]| port_arg(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&["olly-molly", "--port", "4567"]), some("4567"));
+        assert_eq!(args(&["olly-molly", "--port=4567"]), some("4567"));
+        assert_eq!(args(&["olly-molly", "--port"]), None);
+        assert_eq!(args(&["olly-molly"]), None);
+    }
+    
+    #[test]
+    fn http_response_status_and_body() {
+        let plain = b"HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: 2\r\n\r\nok";
+        assert_eq!(parse_http_response(plain), Some((200, "ok".to_string())));
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n1\r\no\r\n1\r\nk\r\n0\r\n\r\n";
+        assert_eq!(parse_http_response(chunked), Some((200, "ok".to_string())));
+        assert_eq!(parse_http_response(b"HTTP/1.1 404 Not Found\r\n\r\n"), Some((404, String::new())));
+        assert_eq!(parse_http_response(b"SSH-2.0-OpenSSH_9.6\r\n"), None);
+        assert_eq!(parse_http_response(b""), None);
+    }
+    
+    #[test]
+    fn health_body_must_say_ok() {
+        assert!(is_ok_body("ok"));
+        assert!(is_ok_body("\"ok\"\n"));
+        assert!(!is_ok_body("<!DOCTYPE html>"));
+        assert!(!is_ok_body(""));
+    }
+    
+    #[test]
+    fn base_path_has_leading_slash_only() {
+        assert_eq!(normalize_base_path(""), "/");
+        assert_eq!(normalize_base_path("/"), "/");
+        assert_eq!(normalize_base_path("app"), "/app");
+        assert_eq!(normalize_base_path(" /app/ "), "/app");
+        assert_eq!(normalize_base_path("/tools/app"), "/tools/app");
+    }
+    
+    #[test]
+    fn config_file_port_reads_number_or_string() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(APP_CONFIG_FILE);
+        assert_eq!(config_file_port(&path), None);
+        
+        std::fs::write(&path, r#"{"port": 4567}"#).unwrap();
+        assert_eq!(config_file_port(&path), some("4567"));
+        std::fs::write(&path, r#"{"port": "4568"}"#).unwrap();
+        assert_eq!(config_file_port(&path), some("4568"));
+        std::fs::write(&path, r#"{"theme": "dark"}"#).unwrap();
+        assert_eq!(config_file_port(&path), None);
+        std::fs::write(&path, "port = 4567").unwrap();
+        assert_eq!(config_file_port(&path), None);
+    }
+    
+    #[test]
+    fn describe_command_hides_server_env_values() {
+        let mut command = Command::new("/usr/bin/node");
+        command
+            .arg("server.js")
+            .current_dir("/app/server")
+            .env("PORT", "1234")
+            .env("API_KEY", "secret");
+        let masked = HashMap::from([("API_KEY".to_string(), "secret".to_string())]);
+        
+        assert_eq!(
+            describe_command(&command, &masked),
+            r#""/usr/bin/node" "server.js" in "/app/server" with API_KEY=<hidden> PORT="1234""#
+        );
+    }
+    
+    #[test]
+    fn server_env_reads_string_map() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(SERVER_ENV_FILE);
+        std::fs::write(&path, r#"{"API_KEY": "secret", "FEATURE_X": "1"}"#).unwrap();
+        
+        let env = load_server_env(&path);
+        
+        assert_eq!(env.len(), 2);
+        assert_eq!(env["API_KEY"], "secret");
+        assert_eq!(env["FEATURE_X"], "1");
+    }
+    
+    #[test]
+    fn server_env_ignores_malformed_or_missing_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(SERVER_ENV_FILE);
+        assert!(load_server_env(&path).is_empty());
+        
+        std::fs::write(&path, r#"{"API_KEY": "#).unwrap();
+        assert!(load_server_env(&path).is_empty());
+        
+        // Values must be strings
+        std::fs::write(&path, r#"{"PORT_OFFSET": 2}"#).unwrap();
+        assert!(load_server_env(&path).is_empty());
+    }
+    
+    #[test]
+    fn server_names_are_safe_directory_names() {
+        assert!(valid_server_name("checkout"));
+        assert!(valid_server_name("micro_frontend-2"));
+        assert!(!valid_server_name(""));
+        assert!(!valid_server_name("../data"));
+        assert!(!valid_server_name("two words"));
+    }
+    
+    #[test]
+    fn node_env_override_beats_server_env() {
+        assert_eq!(choose_node_env(None, None), "production");
+        assert_eq!(choose_node_env(None, Some("test")), "test");
+        assert_eq!(choose_node_env(some(" development "), Some("test")), "development");
+        assert_eq!(choose_node_env(some(""), None), "production");
+    }
+    
+    #[test]
+    fn prefixed_env_strips_prefix() {
+        let vars = [
+            ("OLLY_ENV_FEATURE_X", "1"),
+            ("OLLY_ENV_", "no name"),
+            ("OLLY_PORT", "4567"),
+            ("PATH", "/usr/bin"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        
+        assert_eq!(prefixed_env(vars), HashMap::from([("FEATURE_X".to_string(), "1".to_string())]));
+    }
+    
+    #[test]
+    fn push_capped_drops_oldest_lines() {
+        let mut lines = VecDeque::new();
+        for line in ["one", "two", "three"] {
+            push_capped(&mut lines, line, 2);
+        }
+        
+        assert_eq!(lines, ["two", "three"]);
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn reap_child_collects_exited_process() {
+        let mut child = Command::new("true").spawn().unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        
+        let status = reap_child(&mut child).unwrap();
+        
+        assert!(status.success());
+        assert_eq!(child.try_wait().unwrap(), Some(status));
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn dropping_managed_server_kills_process() {
+        let server = ManagedServer(Command::new("sleep").arg("30").spawn().unwrap());
+        let pid = server.id() as libc::pid_t;
+        
+        drop(server);
+        
+        // Reaped, so the PID no longer exists (not even as a zombie)
+        assert_eq!(unsafe { libc::kill(pid, 0) }, -1);
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn reap_child_kills_running_process() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        
+        let status = reap_child(&mut child).unwrap();
+        
+        assert!(!status.success());
+        assert_eq!(child.try_wait().unwrap(), Some(status));
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn terminate_gracefully_lets_child_exit() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        
+        let shutdown = terminate_gracefully(&mut child, Duration::from_secs(5)).unwrap();
+        
+        assert!(matches!(shutdown, Shutdown::Graceful(_)), "{}", shutdown);
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn terminate_gracefully_kills_child_ignoring_sigterm() {
+        let mut child = Command::new("sh").args(["-c", "trap '' TERM; exec sleep 30"]).spawn().unwrap();
+        // Give the shell time to install the trap
+        std::thread::sleep(Duration::from_millis(200));
+        let grace = Duration::from_millis(300);
+        let started = Instant::now();
+        
+        let shutdown = terminate_gracefully(&mut child, grace).unwrap();
+        
+        assert!(matches!(shutdown, Shutdown::Forced(_)), "{}", shutdown);
+        assert!(started.elapsed() >= grace);
+        assert!(child.try_wait().unwrap().is_some());
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn terminate_all_gracefully_shares_one_grace_period() {
+        let mut children: Vec<Child> = (0..3)
+            .map(|_| Command::new("sh").args(["-c", "trap '' TERM; exec sleep 30"]).spawn().unwrap())
+            .collect();
+        std::thread::sleep(Duration::from_millis(200));
+        let grace = Duration::from_millis(500);
+        let started = Instant::now();
+        
+        let shutdowns = terminate_all_gracefully(&mut children.iter_mut().collect::<Vec<_>>(), grace);
+        
+        assert!(started.elapsed() < grace * 2, "took {:?}", started.elapsed());
+        for (child, shutdown) in children.iter_mut().zip(shutdowns) {
+            assert!(matches!(shutdown.unwrap(), Shutdown::Forced(_)));
+            assert!(child.try_wait().unwrap().is_some());
+        }
+    }
+    
+    #[cfg(windows)]
+    #[test]
+    fn terminate_gracefully_stops_windowless_child() {
+        // Without a console window WM_CLOSE has nowhere to go, so this ends
+        // with taskkill /F
+        let mut child = command("ping").args(["-n", "30", "127.0.0.1"]).stdout(Stdio::piped()).spawn().unwrap();
+        
+        let shutdown = terminate_gracefully(&mut child, Duration::from_millis(500)).unwrap();
+        
+        assert!(matches!(shutdown, Shutdown::Forced(_)), "{}", shutdown);
+        assert!(child.try_wait().unwrap().is_some());
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn reap_child_kills_process_group() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "sleep 30 & wait"]).stdout(Stdio::piped());
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut child = command.spawn().unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        
+        reap_child(&mut child).unwrap();
+        
+        // The pipe only closes once the backgrounded sleep is gone too
+        let (done, closed) = std::sync::mpsc::channel();
+        std::thread::spawn(move || done.send(stdout.read_to_end(&mut Vec::new()).is_ok()));
+        assert_eq!(closed.recv_timeout(Duration::from_secs(5)), Ok(true));
+    }
+    
+    /// Stands in for Node.js: binds `port` and fails the way Next.js does when
+    /// it is taken. Returns the listener so the port stays occupied.
+    fn bind_like_next(port: u16) -> Result<TcpListener, ServerError> {
+        TcpListener::bind((DEFAULT_HOSTNAME, port)).map_err(|_| ServerError::PortInUse { port, holder: None })
+    }
+    
+    #[test]
+    fn retry_spawn_retries_transient_failures() {
+        let mut calls = 0;
+        let result = retry_spawn(3, |_| Duration::ZERO, || {
+            calls += 1;
+            if calls < 3 {
+                Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+            } else {
+                Ok(calls)
+            }
+        });
+        
+        assert_eq!(result.unwrap(), 3);
+    }
+    
+    #[test]
+    fn retry_spawn_gives_up_after_attempts() {
+        let mut calls = 0;
+        let result: std::io::Result<()> = retry_spawn(3, |_| Duration::ZERO, || {
+            calls += 1;
+            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+        });
+        
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::PermissionDenied);
+        assert_eq!(calls, 3);
+    }
+    
+    #[test]
+    fn spawn_backoff_doubles_from_250ms() {
+        let delays: Vec<u128> = (1..SPAWN_ATTEMPTS).map(|retry| spawn_backoff(retry).as_millis()).collect();
+        assert_eq!(delays, [250, 500, 1000]);
+    }
+    
+    #[test]
+    fn retry_spawn_does_not_retry_missing_program() {
+        let mut calls = 0;
+        let result: std::io::Result<()> = retry_spawn(3, |_| Duration::ZERO, || {
+            calls += 1;
+            Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+        });
+        
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+    
+    fn timed_out(port: u16) -> ServerError {
+        ServerError::StartupTimeout { port, timeout: Duration::ZERO, stderr_tail: format!("attempt on {}", port) }
+    }
+    
+    #[test]
+    fn startup_policy_prefers_env_over_config_file() {
+        let policy = StartupPolicy::from_settings(|var, key| match (var, key) {
+            ("OLLY_STARTUP_ATTEMPTS", _) => Some((var.to_string(), "5".to_string())),
+            (_, "startup_timeout_ms") => Some((key.to_string(), "60000".to_string())),
+            (_, "startup_budget_ms") => Some((key.to_string(), "soon".to_string())),
+            _ => None,
+        });
+        
+        assert_eq!(
+            policy,
+            StartupPolicy {
+                attempts: 5,
+                attempt_timeout: Duration::from_secs(60),
+                budget: DEFAULT_STARTUP_BUDGET,
+            }
+        );
+        assert_eq!(StartupPolicy::from_settings(|var, _| Some((var.to_string(), "0".to_string()))).attempts, 1);
+    }
+    
+    #[test]
+    fn retry_startup_retries_until_ready() {
+        let policy = StartupPolicy {
+            attempts: 3,
+            attempt_timeout: Duration::from_secs(30),
+            budget: Duration::from_secs(60),
+        };
+        let mut timeouts = Vec::new();
+        let result = retry_startup(&policy, |timeout| {
+            timeouts.push(timeout);
+            if timeouts.len() < 3 {
+                Err(timed_out(1234))
+            } else {
+                Ok(())
+            }
+        });
+        
+        assert!(result.is_ok());
+        assert_eq!(timeouts[0], Duration::from_secs(30));
+        // Later attempts only get what is left of the budget
+        assert!(timeouts[2] <= Duration::from_secs(30));
+    }
+    
+    #[test]
+    fn retry_startup_reports_every_failed_attempt() {
+        let policy = StartupPolicy {
+            attempts: 2,
+            attempt_timeout: Duration::from_secs(1),
+            budget: Duration::from_secs(10),
+        };
+        let result: Result<(), ServerError> = retry_startup(&policy, |_| Err(timed_out(1234)));
+        
+        let Err(ServerError::StartupFailed { attempts }) = result else { panic!("expected StartupFailed") };
+        assert_eq!(attempts.len(), 2);
+        let message = ServerError::StartupFailed { attempts }.to_string();
+        assert!(message.contains("Attempt 1: ") && message.contains("Attempt 2: "), "{}", message);
+    }
+    
+    #[test]
+    fn retry_startup_does_not_retry_other_errors() {
+        let policy = StartupPolicy {
+            attempts: 3,
+            attempt_timeout: Duration::from_secs(1),
+            budget: Duration::from_secs(10),
+        };
+        let mut attempts = 0;
+        let result: Result<(), ServerError> = retry_startup(&policy, |_| {
+            attempts += 1;
+            Err(ServerError::PortInUse { port: 1234, holder: None })
+        });
+        
+        assert!(matches!(result, Err(ServerError::PortInUse { .. })));
+        assert_eq!(attempts, 1);
+    }
+    
+    #[test]
+    fn first_attempt_gets_a_timeout_longer_than_the_budget() {
+        let policy = StartupPolicy {
+            attempts: 3,
+            attempt_timeout: Duration::from_secs(300),
+            budget: Duration::from_secs(120),
+        };
+        let mut timeouts = Vec::new();
+        let result = retry_startup(&policy, |timeout| {
+            timeouts.push(timeout);
+            Ok(())
+        });
+        
+        assert!(result.is_ok());
+        assert_eq!(timeouts, [Duration::from_secs(300)]);
+    }
+    
+    #[test]
+    fn retry_startup_stops_when_budget_is_used_up() {
+        let policy = StartupPolicy {
+            attempts: 3,
+            attempt_timeout: Duration::from_secs(1),
+            budget: Duration::ZERO,
+        };
+        let mut timeouts = Vec::new();
+        let result: Result<(), ServerError> = retry_startup(&policy, |timeout| {
+            timeouts.push(timeout);
+            Err(timed_out(1234))
+        });
+        
+        assert!(matches!(result, Err(ServerError::StartupTimeout { .. })));
+        // A zero budget still lets the first attempt run its course
+        assert_eq!(timeouts, [Duration::from_secs(1)]);
+    }
+    
+    #[test]
+    fn port_listeners_are_parsed() {
+        let lsof = concat!(
+            "COMMAND  PID USER   FD   TYPE DEVICE SIZE/OFF NODE NAME\n",
+            "node    4321 olly   23u  IPv4 0x1234      0t0  TCP 127.0.0.1:3000 (LISTEN)\n",
+        );
+        assert_eq!(parse_lsof_listeners(lsof), vec![(Some("node".to_string()), 4321)]);
+        assert_eq!(parse_lsof_listeners(""), vec![]);
+        
+        let netstat = concat!(
+            "  TCP    0.0.0.0:135        0.0.0.0:0        LISTENING      1000\r\n",
+            "  TCP    127.0.0.1:3000     0.0.0.0:0        LISTENING      4321\r\n",
+            "  TCP    127.0.0.1:30000    0.0.0.0:0        LISTENING      5555\r\n",
+            "  TCP    127.0.0.1:50123    127.0.0.1:3000   ESTABLISHED    777\r\n",
+        );
+        assert_eq!(parse_netstat_listeners(netstat, 3000), vec![(None, 4321)]);
+    }
+    
+    #[test]
+    fn launch_avoids_occupied_preferred_port() {
+        let occupied = TcpListener::bind((DEFAULT_HOSTNAME, 0)).unwrap();
+        let occupied_port = occupied.local_addr().unwrap().port();
+        
+        let (_listener, port) = launch_on_free_port(occupied_port, bind_like_next).unwrap();
+        assert_ne!(port, occupied_port);
+    }
+    
+    #[test]
+    fn launch_retries_when_port_is_taken_after_the_check() {
+        let mut occupied = None;
+        let mut attempts = Vec::new();
+        let (_listener, port) = launch_on_free_port(0, |port| {
+            attempts.push(port);
+            // Someone else binds the port right after find_free_port checked it
+            if occupied.is_none() {
+                occupied = Some(TcpListener::bind((DEFAULT_HOSTNAME, port)).unwrap());
+            }
+            bind_like_next(port)
+        })
+        .unwrap();
+        
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[1], port);
+        assert_ne!(attempts[0], port);
+    }
+    
+    #[test]
+    fn launch_gives_up_after_port_attempts() {
+        let mut attempts = 0;
+        let result = launch_on_free_port(0, |port| -> Result<(), ServerError> {
+            attempts += 1;
+            Err(ServerError::PortInUse { port, holder: None })
+        });
+        
+        assert!(matches!(result, Err(ServerError::PortInUse { .. })));
+        assert_eq!(attempts, PORT_ATTEMPTS);
+    }
+    
+    #[test]
+    fn launch_does_not_retry_other_errors() {
+        let mut attempts = 0;
+        let result = launch_on_free_port(0, |_| -> Result<(), ServerError> {
+            attempts += 1;
+            Err(ServerError::RestartInProgress)
+        });
+        
+        assert!(matches!(result, Err(ServerError::RestartInProgress)));
+        assert_eq!(attempts, 1);
+    }
+    
+    fn server() -> &'static Path {
+        Path::new(DEFAULT_SERVER_SUBDIR)
+    }
+    
+    #[test]
+    fn resolve_server_dir_prefers_first_complete_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (first, second) = (tmp.path().join("first"), tmp.path().join("second"));
+        touch(&first.join("server").join("server.js"));
+        touch(&second.join("server").join("server.js"));
+        
+        let found = resolve_server_dir(&[first.clone(), second], server());
+        assert_eq!(found, Some(first.join("server")));
+    }
+    
+    #[test]
+    fn resolve_server_dir_skips_copy_without_server_js() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (stale, complete) = (tmp.path().join("stale"), tmp.path().join("complete"));
+        std::fs::create_dir_all(stale.join("server")).unwrap();
+        touch(&complete.join("server").join("server.js"));
+        
+        let found = resolve_server_dir(&[stale, complete.clone()], server());
+        assert_eq!(found, Some(complete.join("server")));
+    }
+    
+    #[test]
+    fn resolve_server_dir_accepts_next_start_build() {
+        let tmp = tempfile::tempdir().unwrap();
+        let server_dir = tmp.path().join("server");
+        touch(&server_dir.join("package.json"));
+        touch(&server_dir.join("node_modules/next/dist/bin/next"));
+        
+        assert_eq!(resolve_server_dir(&[tmp.path().to_path_buf()], server()), Some(server_dir));
+    }
+    
+    #[test]
+    fn launch_strategy_prefers_standalone_server() {
+        let tmp = tempfile::tempdir().unwrap();
+        let next_bin = tmp.path().join("node_modules/next/dist/bin/next");
+        assert_eq!(launch_strategy(tmp.path()), None);
+        
+        touch(&tmp.path().join("package.json"));
+        assert_eq!(launch_strategy(tmp.path()), None);
+        touch(&next_bin);
+        assert_eq!(launch_strategy(tmp.path()), Some(LaunchStrategy::NextStart(next_bin)));
+        touch(&tmp.path().join("server.js"));
+        assert_eq!(launch_strategy(tmp.path()), Some(LaunchStrategy::Standalone(tmp.path().join("server.js"))));
+    }
+    
+    #[test]
+    fn resolve_server_dir_falls_back_to_incomplete_copy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (missing, stale) = (tmp.path().join("missing"), tmp.path().join("stale"));
+        std::fs::create_dir_all(stale.join("server")).unwrap();
+        
+        assert_eq!(resolve_server_dir(&[missing, stale.clone()], server()), Some(stale.join("server")));
+    }
+    
+    #[test]
+    fn resolve_server_dir_none_without_server_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        
+        assert_eq!(resolve_server_dir(&[tmp.path().to_path_buf()], server()), None);
+        assert_eq!(resolve_server_dir(&[], server()), None);
+    }
+    
+    #[test]
+    fn resolve_server_dir_uses_given_subdir() {
+        let tmp = tempfile::tempdir().unwrap();
+        touch(&tmp.path().join("server").join("server.js"));
+        touch(&tmp.path().join("backends").join("web").join("server.js"));
+        
+        let subdir = Path::new("backends/web");
+        let roots = [tmp.path().to_path_buf()];
+        assert_eq!(resolve_server_dir(&roots, subdir), Some(tmp.path().join(subdir)));
+    }
+    
+    #[test]
+    fn find_server_dir_in_prefers_resource_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (resources, exe_dir) = (tmp.path().join("resources"), tmp.path().join("bin"));
+        touch(&resources.join("server").join("server.js"));
+        touch(&exe_dir.join("server").join("server.js"));
+        
+        let found = find_server_dir_in(Some(&resources), Some(&exe_dir), server()).unwrap();
+        assert_eq!(found, resources.join("server"));
+    }
+    
+    #[test]
+    fn find_server_dir_in_falls_back_to_exe_resources() {
+        let tmp = tempfile::tempdir().unwrap();
+        let contents = tmp.path().join("Olly Molly.app").join("Contents");
+        let exe_dir = contents.join("MacOS");
+        // Contents/Resources on macOS, next to the executable elsewhere
+        let resources = if cfg!(any(windows, target_os = "linux")) {
+            exe_dir.clone()
+        } else {
+            contents.join("Resources")
+        };
+        touch(&resources.join("server").join("server.js"));
+        
+        let found = find_server_dir_in(Some(&tmp.path().join("missing")), Some(&exe_dir), server()).unwrap();
+        assert_eq!(found, resources.join("server"));
+    }
+    
+    #[test]
+    fn find_server_dir_in_reports_searched_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let resources = tmp.path().join("resources");
+        std::fs::create_dir_all(&resources).unwrap();
+        
+        match find_server_dir_in(Some(&resources), Some(&tmp.path().join("bin")), server()) {
+            Err(ServerError::ServerDirNotFound { searched }) => {
+                assert_eq!(searched.first(), Some(&resources.join("server")));
+                assert!(searched.len() > 1);
+            }
+            other => panic!("expected ServerDirNotFound, got {:?}", other),
+        }
+        assert!(matches!(find_server_dir_in(None, None, server()), Err(ServerError::ServerDirNotFound { .. })));
+    }
+    
+    #[test]
+    fn macos_bundle_uses_contents_resources() {
+        let tmp = tempfile::tempdir().unwrap();
+        let contents = tmp.path().join("Olly Molly.app").join("Contents");
+        touch(&contents.join("MacOS").join("olly-molly"));
+        touch(&contents.join("Resources").join("server").join("server.js"));
+        
+        let roots = macos_resource_dirs(&contents.join("MacOS"));
+        assert_eq!(resolve_server_dir(&roots, server()), Some(contents.join("Resources").join("server")));
+    }
+    
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_deb_layout_uses_usr_lib() {
+        let tmp = tempfile::tempdir().unwrap();
+        let usr = tmp.path().join("usr");
+        touch(&usr.join("lib").join("Olly Molly").join("server").join("server.js"));
+        
+        let roots = linux_resource_dirs(&usr.join("bin"), None);
+        let found = resolve_server_dir(&roots, server());
+        assert_eq!(found, Some(usr.join("lib").join("Olly Molly").join("server")));
+    }
+    
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_appimage_layout_uses_appdir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let appdir = tmp.path().join("squashfs-root");
+        let server_dir = appdir.join("usr").join("lib").join(env!("CARGO_PKG_NAME")).join("server");
+        touch(&server_dir.join("server.js"));
+        
+        // The executable may be a copy outside the mount, so only APPDIR leads there
+        let roots = linux_resource_dirs(&tmp.path().join("elsewhere"), Some(&appdir));
+        assert_eq!(resolve_server_dir(&roots, server()), Some(server_dir));
+    }
+    
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_tarball_layout_uses_exe_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app_dir = tmp.path().join("olly-molly");
+        touch(&app_dir.join("server").join("server.js"));
+        
+        let roots = linux_resource_dirs(&app_dir, None);
+        assert_eq!(resolve_server_dir(&roots, server()), Some(app_dir.join("server")));
+    }
 }
@@ -0,0 +1,264 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SERVER_LOG_NAME: &str = "next-server";
+const SERVER_LOG_FILES: usize = 5;
+const SERVER_LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+const DEFAULT_LOG_BUFFER_LINES: usize = 2000;
+
+/// A line of server output as kept in memory, for `get_recent_logs`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct LogLine {
+    /// Milliseconds since the Unix epoch
+    pub(crate) at: u64,
+    /// `"stdout"` or `"stderr"`
+    pub(crate) stream: &'static str,
+    /// The server process that printed it, to tell instances apart across
+    /// restarts
+    pub(crate) pid: u32,
+    pub(crate) line: String,
+}
+
+/// The most recent lines of server output, oldest first. Shared by every
+/// server instance of this app run, so a crashed server's last words are
+/// still there after the restart.
+pub(crate) struct LogBuffer {
+    lines: VecDeque<LogLine>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub(crate) fn new(capacity: usize) -> LogBuffer {
+        LogBuffer { lines: VecDeque::with_capacity(capacity), capacity }
+    }
+    
+    /// Sized from `OLLY_LOG_BUFFER_LINES`, 2000 lines by default.
+    pub(crate) fn from_env() -> LogBuffer {
+        let capacity = match std::env::var("OLLY_LOG_BUFFER_LINES") {
+            Ok(value) => match value.trim().parse::<usize>() {
+                Ok(lines) if lines > 0 => lines,
+                _ => {
+                    log::warn!(
+                        "Ignoring invalid OLLY_LOG_BUFFER_LINES={:?}, keeping {} lines",
+                        value,
+                        DEFAULT_LOG_BUFFER_LINES
+                    );
+                    DEFAULT_LOG_BUFFER_LINES
+                }
+            },
+            Err(_) => DEFAULT_LOG_BUFFER_LINES,
+        };
+        LogBuffer::new(capacity)
+    }
+    
+    /// Appends a line, dropping the oldest once full. The dropped line's
+    /// string is reused, so a chatty server doesn't allocate per line.
+    pub(crate) fn push(&mut self, stream: &'static str, pid: u32, line: &str) {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_millis() as u64)
+            .unwrap_or_default();
+        let mut text = if self.lines.len() == self.capacity {
+            self.lines.pop_front().map(|oldest| oldest.line).unwrap_or_default()
+        } else {
+            String::new()
+        };
+        text.clear();
+        text.push_str(line);
+        self.lines.push_back(LogLine { at, stream, pid, line: text });
+    }
+    
+    /// The last `limit` lines, oldest first.
+    pub(crate) fn recent(&self, limit: usize) -> impl Iterator<Item = &LogLine> {
+        self.lines.iter().skip(self.lines.len().saturating_sub(limit))
+    }
+}
+
+/// Server output persisted under the app log dir. `next-server.log` is the
+/// file being written, `next-server.1.log` the one before and so on, up to
+/// `SERVER_LOG_FILES` files. A new file is started for every app run and
+/// whenever the current one grows past `SERVER_LOG_MAX_BYTES`.
+pub(crate) struct ServerLog {
+    dir: PathBuf,
+    path: PathBuf,
+    max_bytes: u64,
+    // Rotation happens under this lock, so the stdout and stderr readers never
+    // write to a file that is being renamed
+    file: Mutex<LogFile>,
+}
+
+struct LogFile {
+    // None if a rotation failed to create the next file
+    writer: Option<LineWriter<File>>,
+    written: u64,
+}
+
+impl ServerLog {
+    /// Rotates the previous runs' logs out of the way and starts a new one.
+    pub(crate) fn open(dir: &Path) -> io::Result<ServerLog> {
+        ServerLog::open_with_limit(dir, SERVER_LOG_MAX_BYTES)
+    }
+    
+    fn open_with_limit(dir: &Path, max_bytes: u64) -> io::Result<ServerLog> {
+        fs::create_dir_all(dir)?;
+        rotate(dir, SERVER_LOG_NAME, SERVER_LOG_FILES)?;
+        let path = run_log_path(dir, SERVER_LOG_NAME, 0);
+        let writer = LineWriter::new(File::create(&path)?);
+        Ok(ServerLog {
+            dir: dir.to_path_buf(),
+            path,
+            max_bytes,
+            file: Mutex::new(LogFile { writer: Some(writer), written: 0 }),
+        })
+    }
+    
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    /// Appends a line of server output, stamped with the UTC time and the
+    /// PID of the server that printed it. `LineWriter` flushes every line, so
+    /// the file is complete even if the app crashes right after.
+    pub(crate) fn write_line(&self, stream: &str, pid: u32, line: &str) {
+        let entry = format!("{} [{} {}] {}\n", timestamp(), pid, stream, line);
+        let mut file = self.file.lock().unwrap();
+        if file.written > 0 && file.written + entry.len() as u64 > self.max_bytes {
+            // Close before renaming; Windows won't rename an open file
+            file.writer.take();
+            file.written = 0;
+            match rotate(&self.dir, SERVER_LOG_NAME, SERVER_LOG_FILES)
+                .and_then(|()| File::create(&self.path))
+            {
+                Ok(next) => file.writer = Some(LineWriter::new(next)),
+                Err(e) => {
+                    log::warn!("Failed to rotate {:?}, no longer saving server output: {}", self.path, e)
+                }
+            }
+        }
+        let Some(writer) = file.writer.as_mut() else { return };
+        // A full disk shouldn't take the server down with it
+        if writer.write_all(entry.as_bytes()).is_ok() {
+            file.written += entry.len() as u64;
+        }
+    }
+}
+
+fn timestamp() -> String {
+    time::OffsetDateTime::now_utc()
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap_or_default()
+}
+
+fn run_log_path(dir: &Path, name: &str, run: usize) -> PathBuf {
+    if run == 0 {
+        dir.join(format!("{}.log", name))
+    } else {
+        dir.join(format!("{}.{}.log", name, run))
+    }
+}
+
+/// Shifts `<name>.log` to `<name>.1.log` and so on, dropping the oldest so
+/// that `keep` files remain once the new log is created.
+fn rotate(dir: &Path, name: &str, keep: usize) -> io::Result<()> {
+    for run in (0..keep.saturating_sub(1)).rev() {
+        let from = run_log_path(dir, name, run);
+        if from.exists() {
+            fs::rename(&from, run_log_path(dir, name, run + 1))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn read(path: PathBuf) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+    
+    fn texts(buffer: &LogBuffer, limit: usize) -> Vec<&str> {
+        buffer.recent(limit).map(|line| line.line.as_str()).collect()
+    }
+    
+    #[test]
+    fn log_buffer_keeps_newest_lines() {
+        let mut buffer = LogBuffer::new(3);
+        for line in ["one", "two", "three", "four"] {
+            buffer.push("stdout", 1, line);
+        }
+        buffer.push("stderr", 2, "five");
+        
+        assert_eq!(texts(&buffer, 10), ["three", "four", "five"]);
+        assert_eq!(texts(&buffer, 2), ["four", "five"]);
+        let newest = buffer.recent(1).next().unwrap();
+        assert_eq!((newest.stream, newest.pid), ("stderr", 2));
+    }
+    
+    #[test]
+    fn log_buffer_recent_with_zero_limit_is_empty() {
+        let mut buffer = LogBuffer::new(3);
+        buffer.push("stdout", 1, "one");
+        
+        assert!(texts(&buffer, 0).is_empty());
+    }
+    
+    /// The file's lines without their timestamps.
+    fn entries(path: PathBuf) -> Vec<String> {
+        read(path)
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1.to_string())
+            .collect()
+    }
+    
+    #[test]
+    fn open_keeps_previous_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        for run in ["first", "second", "third"] {
+            ServerLog::open(dir.path()).unwrap().write_line("stdout", 7, run);
+        }
+        
+        assert_eq!(entries(run_log_path(dir.path(), SERVER_LOG_NAME, 0)), ["[7 stdout] third"]);
+        assert_eq!(entries(run_log_path(dir.path(), SERVER_LOG_NAME, 1)), ["[7 stdout] second"]);
+        assert_eq!(entries(run_log_path(dir.path(), SERVER_LOG_NAME, 2)), ["[7 stdout] first"]);
+    }
+    
+    #[test]
+    fn write_line_starts_new_file_past_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        // Room for one long line plus the short one, but not both long ones
+        let server_log = ServerLog::open_with_limit(dir.path(), 300).unwrap();
+        let (first, second) = ("a".repeat(150), "b".repeat(150));
+        server_log.write_line("stdout", 1, &first);
+        server_log.write_line("stderr", 1, &second);
+        server_log.write_line("stdout", 2, "after restart");
+        
+        assert_eq!(
+            entries(run_log_path(dir.path(), SERVER_LOG_NAME, 0)),
+            [format!("[1 stderr] {}", second), "[2 stdout] after restart".to_string()]
+        );
+        assert_eq!(
+            entries(run_log_path(dir.path(), SERVER_LOG_NAME, 1)),
+            [format!("[1 stdout] {}", first)]
+        );
+    }
+    
+    #[test]
+    fn rotate_drops_runs_beyond_keep() {
+        let dir = tempfile::tempdir().unwrap();
+        for run in 0..3 {
+            fs::write(run_log_path(dir.path(), "server", run), run.to_string()).unwrap();
+        }
+        
+        rotate(dir.path(), "server", 3).unwrap();
+        
+        assert!(!run_log_path(dir.path(), "server", 0).exists());
+        assert_eq!(read(run_log_path(dir.path(), "server", 1)), "0");
+        assert_eq!(read(run_log_path(dir.path(), "server", 2)), "1");
+        assert!(!run_log_path(dir.path(), "server", 3).exists());
+    }
+}
@@ -0,0 +1,605 @@
+#[cfg(unix)]
+use std::io::Read;
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::process::Stdio;
+#[cfg(unix)]
+use std::sync::OnceLock;
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+
+use crate::command::command;
+use crate::error::ServerError;
+
+/// Oldest Node.js that Next.js 14 supports; override with `OLLY_MIN_NODE_VERSION`.
+const DEFAULT_MIN_NODE_VERSION: (u32, u32, u32) = (18, 17, 0);
+
+/// The Node.js binary the server runs on, reported in `server_status`.
+#[derive(Clone, serde::Serialize)]
+pub(crate) struct NodeRuntime {
+    pub(crate) path: PathBuf,
+    pub(crate) version: String,
+}
+
+/// Well-known Node.js install locations, checked before falling back to a
+/// PATH lookup.
+#[cfg(target_os = "macos")]
+fn node_candidate_paths() -> Vec<PathBuf> {
+    let mut paths = vec![
+        PathBuf::from("/usr/local/bin/node"),
+        PathBuf::from("/opt/homebrew/bin/node"),
+        PathBuf::from("/usr/bin/node"),
+    ];
+    if let Some(home) = std::env::var_os("HOME") {
+        paths.extend(version_manager_nodes(Path::new(&home)));
+    }
+    paths
+}
+
+#[cfg(target_os = "linux")]
+fn node_candidate_paths() -> Vec<PathBuf> {
+    let mut paths = vec![
+        PathBuf::from("/usr/bin/node"),
+        PathBuf::from("/usr/local/bin/node"),
+        // Official binary tarball extracted to /usr/local/node
+        PathBuf::from("/usr/local/node/bin/node"),
+        PathBuf::from("/snap/bin/node"),
+    ];
+    if let Some(home) = std::env::var_os("HOME") {
+        paths.extend(version_manager_nodes(Path::new(&home)));
+    }
+    paths
+}
+
+#[cfg(target_os = "windows")]
+fn node_candidate_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for var in ["ProgramFiles", "ProgramFiles(x86)"] {
+        if let Some(dir) = std::env::var_os(var) {
+            paths.push(PathBuf::from(dir).join("nodejs").join("node.exe"));
+        }
+    }
+    // nvm-windows: the active version is symlinked to NVM_SYMLINK, and every
+    // installed version lives under %APPDATA%\nvm\vX.Y.Z
+    if let Some(symlink) = std::env::var_os("NVM_SYMLINK") {
+        paths.push(PathBuf::from(symlink).join("node.exe"));
+    }
+    if let Some(app_data) = std::env::var_os("APPDATA") {
+        let app_data = PathBuf::from(app_data);
+        paths.extend(newest_subdir(&app_data.join("nvm")).map(|dir| dir.join("node.exe")));
+        // npm global prefix, where some installers drop node.exe alongside the shims
+        paths.push(app_data.join("npm").join("node.exe"));
+    }
+    // Package managers: Scoop installs per user, Chocolatey shims system-wide
+    if let Some(profile) = std::env::var_os("USERPROFILE") {
+        paths.push(PathBuf::from(profile).join(r"scoop\apps\nodejs\current\node.exe"));
+    }
+    if let Some(program_data) = std::env::var_os("ProgramData") {
+        paths.push(PathBuf::from(program_data).join(r"chocolatey\bin\node.exe"));
+    }
+    // GUI apps launched before an install finished still see the old PATH, so
+    // also consult the PATH currently stored in the registry
+    paths.extend(registry_path_dirs().into_iter().map(|dir| dir.join("node.exe")));
+    paths
+}
+
+/// Reads the user and system `Path` values from the registry via `reg query`.
+#[cfg(target_os = "windows")]
+fn registry_path_dirs() -> Vec<PathBuf> {
+    let keys = [
+        r"HKCU\Environment",
+        r"HKLM\SYSTEM\CurrentControlSet\Control\Session Manager\Environment",
+    ];
+    let mut dirs = Vec::new();
+    for key in keys {
+        let Ok(output) = command("reg").args(["query", key, "/v", "Path"]).output() else {
+            continue;
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        // Value lines look like: "    Path    REG_EXPAND_SZ    C:\foo;%USERPROFILE%\bar"
+        for line in stdout.lines() {
+            let Some((_, value)) = line.split_once("REG_") else { continue };
+            let Some((_, value)) = value.split_once(char::is_whitespace) else { continue };
+            dirs.extend(
+                value
+                    .trim()
+                    .split(';')
+                    .filter(|dir| !dir.is_empty())
+                    .map(|dir| PathBuf::from(expand_env_vars(dir))),
+            );
+        }
+    }
+    dirs
+}
+
+/// Expands `%VAR%` references the way REG_EXPAND_SZ values expect.
+#[cfg(target_os = "windows")]
+fn expand_env_vars(value: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('%') {
+        let Some(len) = rest[start + 1..].find('%') else { break };
+        let name = &rest[start + 1..start + 1 + len];
+        expanded.push_str(&rest[..start]);
+        match std::env::var(name) {
+            Ok(var) => expanded.push_str(&var),
+            Err(_) => expanded.push_str(&rest[start..start + len + 2]),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+fn node_candidate_paths() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// Per-version install directories of Node.js version managers (nvm, fnm on
+/// Linux and macOS, asdf), relative to the home directory:
+/// `(versions dir, binary inside a version dir)`.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+const VERSIONED_NODE_DIRS: [(&str, &str); 4] = [
+    (".nvm/versions/node", "bin/node"),
+    (".local/share/fnm/node-versions", "installation/bin/node"),
+    ("Library/Application Support/fnm/node-versions", "installation/bin/node"),
+    (".asdf/installs/nodejs", "bin/node"),
+];
+
+/// Node.js installed through nvm, fnm, asdf or volta under `home`. These tools
+/// hook into the shell's PATH, which apps launched from the desktop never see,
+/// so look in their install directories directly. Versioned installs come
+/// newest first, followed by the volta and asdf shims, which resolve the
+/// version themselves.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn version_manager_nodes(home: &Path) -> Vec<PathBuf> {
+    let mut installs: Vec<((u32, u32, u32), PathBuf)> = VERSIONED_NODE_DIRS
+        .iter()
+        .flat_map(|&(versions_dir, binary)| {
+            let entries = std::fs::read_dir(home.join(versions_dir)).into_iter().flatten();
+            entries.filter_map(move |entry| {
+                let entry = entry.ok()?;
+                // Version dirs are named "v20.11.0" (nvm, fnm) or "20.11.0" (asdf)
+                let version = parse_node_version(&entry.file_name().to_string_lossy())?;
+                let node_path = entry.path().join(binary);
+                node_path.is_file().then_some((version, node_path))
+            })
+        })
+        .collect();
+    installs.sort_by_key(|(version, _)| std::cmp::Reverse(*version));
+    
+    let mut paths: Vec<PathBuf> = installs.into_iter().map(|(_, node_path)| node_path).collect();
+    let shims = [
+        home.join(".volta").join("bin").join("node"),
+        home.join(".asdf").join("shims").join("node"),
+    ];
+    paths.extend(shims.into_iter().filter(|shim| shim.is_file()));
+    paths
+}
+
+/// Returns the version-named subdirectory of `dir` with the highest version.
+/// Compared numerically, so v9 doesn't beat v18 like it would as a string.
+#[cfg(target_os = "windows")]
+fn newest_subdir(dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_dir())
+        .filter_map(|path| {
+            let version = parse_node_version(&path.file_name()?.to_string_lossy())?;
+            Some((version, path))
+        })
+        .max_by_key(|(version, _)| *version)
+        .map(|(_, path)| path)
+}
+
+/// Tool used to look `node` up on PATH.
+#[cfg(windows)]
+const PATH_LOOKUP_COMMAND: &str = "where.exe";
+#[cfg(not(windows))]
+const PATH_LOOKUP_COMMAND: &str = "which";
+
+/// Node.js shipped as a Tauri sidecar (`bundle.externalBin`, see
+/// `tauri.sidecar.conf.json`). The bundler strips the target-triple suffix and
+/// places it next to the app executable. We spawn it with `std::process` like
+/// a system install rather than through the shell plugin's sidecar API, so
+/// stopping, restarting and log forwarding work the same for both.
+fn sidecar_node_binary() -> Option<PathBuf> {
+    let exe_path = std::env::current_exe().ok()?;
+    let node_name = if cfg!(windows) { "node.exe" } else { "node" };
+    let node_path = exe_path.parent()?.join(node_name);
+    node_path.is_file().then_some(node_path)
+}
+
+/// Whether to look for a system Node.js install when no sidecar is bundled.
+/// On by default; set `OLLY_USE_SYSTEM_NODE=0` to require the sidecar.
+fn system_node_allowed() -> bool {
+    !matches!(
+        std::env::var("OLLY_USE_SYSTEM_NODE").as_deref(),
+        Ok("0") | Ok("false")
+    )
+}
+
+/// Node.js copied into the bundled resources by the build scripts, either
+/// inside the server directory or next to it.
+fn resource_node_binary(server_dir: &Path) -> Option<PathBuf> {
+    let node_name = if cfg!(windows) { "node.exe" } else { "node" };
+    let candidates = [
+        Some(server_dir.join(node_name)),
+        server_dir.parent().map(|dir| dir.join(node_name)),
+    ];
+    let node_path = candidates.into_iter().flatten().find(|path| path.is_file())?;
+    ensure_executable(&node_path);
+    Some(node_path)
+}
+
+/// Portable Node.js distribution shipped under `<resources>/node/`, used as a
+/// last resort when no other Node.js is available. The archive layout differs
+/// by platform: `bin/node` on Unix, `node.exe` at the top level on Windows.
+fn portable_node_binary(resource_dir: &Path) -> Option<PathBuf> {
+    let node_dir = resource_dir.join("node");
+    let node_path = if cfg!(windows) {
+        node_dir.join("node.exe")
+    } else {
+        node_dir.join("bin").join("node")
+    };
+    if !node_path.is_file() {
+        return None;
+    }
+    ensure_executable(&node_path);
+    Some(node_path)
+}
+
+/// Resource copies don't always keep their mode bits (zip, some installers),
+/// so restore the executable bit before trying to run the binary.
+#[cfg(unix)]
+fn ensure_executable(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    
+    let Ok(metadata) = std::fs::metadata(path) else { return };
+    let mut permissions = metadata.permissions();
+    if permissions.mode() & 0o111 == 0 {
+        permissions.set_mode(permissions.mode() | 0o755);
+        if let Err(e) = std::fs::set_permissions(path, permissions) {
+            log::warn!("Failed to make {:?} executable: {}", path, e);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn ensure_executable(_path: &Path) {}
+
+/// Finds a Node.js of at least `minimum`, trying the bundled sidecar, a copy
+/// in the resources, system installs and finally the portable build. Binaries
+/// that are too old or won't run are skipped, so an outdated /usr/bin/node
+/// doesn't shadow a newer install elsewhere. As a last resort the login
+/// shell's PATH is searched.
+pub(crate) fn find_node_binary(server_dir: &Path, minimum: (u32, u32, u32)) -> Result<NodeRuntime, ServerError> {
+    let mut candidates: Vec<(&str, PathBuf)> = Vec::new();
+    candidates.extend(sidecar_node_binary().map(|path| ("bundled sidecar", path)));
+    candidates.extend(resource_node_binary(server_dir).map(|path| ("bundled", path)));
+    if system_node_allowed() {
+        candidates.extend(system_node_candidates().into_iter().map(|path| ("system", path)));
+    }
+    // The server lives at <resources>/server, so its parent is the resource dir
+    candidates.extend(server_dir.parent().and_then(portable_node_binary).map(|path| ("portable", path)));
+    
+    let mut rejected: Option<ServerError> = None;
+    if let Some(node) = first_usable(&candidates, minimum, &mut rejected) {
+        return Ok(node);
+    }
+    // Asking the shell is slow, so only once everything else failed
+    if system_node_allowed() {
+        let checked: Vec<&PathBuf> = candidates.iter().map(|(_, path)| path).collect();
+        let shell_nodes: Vec<(&str, PathBuf)> = login_shell_nodes()
+            .into_iter()
+            .filter(|path| !checked.contains(&path))
+            .map(|path| ("login shell PATH", path))
+            .collect();
+        if let Some(node) = first_usable(&shell_nodes, minimum, &mut rejected) {
+            return Ok(node);
+        }
+    }
+    
+    log::error!("Could not find Node.js {} or newer", format_version(minimum));
+    Err(rejected.unwrap_or(ServerError::NodeNotFound { required: minimum }))
+}
+
+/// The first candidate that runs and is at least `minimum`. Rejections are
+/// kept in `rejected`, preferring an outdated install over one that didn't
+/// run, since upgrading fixes it.
+fn first_usable(
+    candidates: &[(&str, PathBuf)],
+    minimum: (u32, u32, u32),
+    rejected: &mut Option<ServerError>,
+) -> Option<NodeRuntime> {
+    for (source, node_path) in candidates {
+        match check_node_version(node_path, minimum) {
+            Ok(version) => {
+                let version = format_version(version);
+                log::info!("Using {} Node.js v{} at {:?}", source, version, node_path);
+                return Some(NodeRuntime { path: node_path.clone(), version });
+            }
+            Err(e) => {
+                log::warn!("Skipping {} Node.js: {}", source, e);
+                if rejected.is_none() || matches!(e, ServerError::NodeTooOld { .. }) {
+                    *rejected = Some(e);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Every system Node.js that exists: well-known locations first, then the
+/// PATH lookup.
+fn system_node_candidates() -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = node_candidate_paths().into_iter().filter(|path| path.exists()).collect();
+    
+    // Try to find node in PATH using 'which' / 'where'
+    if let Ok(output) = command(PATH_LOOKUP_COMMAND).arg("node").output() {
+        if output.status.success() {
+            // `where` lists every match, one per line
+            for line in String::from_utf8_lossy(&output.stdout).lines() {
+                let path = PathBuf::from(line.trim());
+                if path.exists() && !found.contains(&path) {
+                    found.push(path);
+                }
+            }
+        }
+    }
+    
+    found
+}
+
+/// Node.js binaries in the directories of the login shell's PATH.
+#[cfg(unix)]
+fn login_shell_nodes() -> Vec<PathBuf> {
+    login_shell_path().iter().map(|dir| dir.join("node")).filter(|path| path.is_file()).collect()
+}
+
+#[cfg(not(unix))]
+fn login_shell_nodes() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// Precedes the PATH in the login shell's output, which may also contain
+/// whatever the user's rc files print.
+#[cfg(unix)]
+const SHELL_PATH_MARKER: &str = "__OLLY_PATH__=";
+#[cfg(unix)]
+const SHELL_PATH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The PATH of the user's login shell. Apps started from Finder or the Dock
+/// get a minimal PATH without e.g. /opt/homebrew/bin, while the shell sees
+/// whatever the user's profile adds. Asked once per app run.
+#[cfg(unix)]
+fn login_shell_path() -> &'static [PathBuf] {
+    static LOGIN_SHELL_PATH: OnceLock<Vec<PathBuf>> = OnceLock::new();
+    LOGIN_SHELL_PATH.get_or_init(|| {
+        let default_shell = if cfg!(target_os = "macos") { "/bin/zsh" } else { "/bin/sh" };
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| default_shell.to_string());
+        let dirs = read_login_shell_output(&shell).map(|output| parse_shell_path(&output)).unwrap_or_default();
+        log::info!("Login shell {} PATH: {:?}", shell, dirs);
+        dirs
+    })
+}
+
+/// Runs `shell` as an interactive login shell that prints its PATH. Gives
+/// up after `SHELL_PATH_TIMEOUT` in case the profile waits on something.
+#[cfg(unix)]
+fn read_login_shell_output(shell: &str) -> Option<String> {
+    // fish keeps PATH as a list
+    let script = if shell.ends_with("fish") {
+        format!("printf '%s%s\\n' '{}' (string join : $PATH)", SHELL_PATH_MARKER)
+    } else {
+        format!("printf '%s%s\\n' '{}' \"$PATH\"", SHELL_PATH_MARKER)
+    };
+    let mut child = command(shell)
+        .args(["-ilc", &script])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| log::warn!("Failed to run login shell {}: {}", shell, e))
+        .ok()?;
+    
+    let deadline = Instant::now() + SHELL_PATH_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(50)),
+            _ => {
+                log::warn!("Login shell {} did not report its PATH within {:?}", shell, SHELL_PATH_TIMEOUT);
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+    let mut output = String::new();
+    child.stdout.take()?.read_to_string(&mut output).ok()?;
+    Some(output)
+}
+
+/// The directories from the marked PATH line of the shell's output.
+#[cfg(unix)]
+fn parse_shell_path(output: &str) -> Vec<PathBuf> {
+    let Some(path) = output.lines().find_map(|line| line.strip_prefix(SHELL_PATH_MARKER)) else {
+        return Vec::new();
+    };
+    path.split(':').filter(|dir| !dir.is_empty()).map(PathBuf::from).collect()
+}
+
+/// Runs `node --version` and returns the parsed `(major, minor, patch)`,
+/// rejecting versions older than `minimum`. Shims sometimes print extra lines
+/// around the version, so the first line that parses wins.
+fn check_node_version(node: &Path, minimum: (u32, u32, u32)) -> Result<(u32, u32, u32), ServerError> {
+    let output = command(node).arg("--version").output().map_err(|e| {
+        ServerError::NodeUnusable(format!("Failed to run {} --version: {}", node.display(), e))
+    })?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    
+    let Some((raw, version)) = stdout
+        .lines()
+        .map(str::trim)
+        .find_map(|line| parse_node_version(line).map(|version| (line, version)))
+    else {
+        return Err(ServerError::NodeUnusable(format!(
+            "Could not parse Node.js version {:?} from {}",
+            stdout.trim(),
+            node.display()
+        )));
+    };
+    if !meets_minimum(raw, version, minimum) {
+        return Err(ServerError::NodeTooOld {
+            found: raw.to_string(),
+            path: node.to_path_buf(),
+            required: minimum,
+        });
+    }
+    Ok(version)
+}
+
+/// Whether `version`, parsed from `raw`, is at least `minimum`. Pre-releases
+/// sort before their release, so 18.17.0-rc.1 does not satisfy 18.17.0.
+fn meets_minimum(raw: &str, version: (u32, u32, u32), minimum: (u32, u32, u32)) -> bool {
+    version > minimum || (version == minimum && !raw.contains('-'))
+}
+
+/// Reads the minimum Node.js version from `OLLY_MIN_NODE_VERSION`, falling
+/// back to `DEFAULT_MIN_NODE_VERSION` when unset or invalid.
+pub(crate) fn min_node_version() -> (u32, u32, u32) {
+    match std::env::var("OLLY_MIN_NODE_VERSION") {
+        Ok(value) => parse_node_version(value.trim()).unwrap_or_else(|| {
+            log::warn!(
+                "Ignoring invalid OLLY_MIN_NODE_VERSION={:?}, using {}",
+                value,
+                format_version(DEFAULT_MIN_NODE_VERSION)
+            );
+            DEFAULT_MIN_NODE_VERSION
+        }),
+        Err(_) => DEFAULT_MIN_NODE_VERSION,
+    }
+}
+
+pub(crate) fn format_version((major, minor, patch): (u32, u32, u32)) -> String {
+    format!("{}.{}.{}", major, minor, patch)
+}
+
+/// Parses `vMAJOR.MINOR.PATCH` as printed by `node --version`.
+fn parse_node_version(raw: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = raw.strip_prefix('v').unwrap_or(raw).splitn(3, '.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    // Nightlies and RCs carry a suffix on the patch, e.g. "0-rc.1"
+    let patch = parts.next()?;
+    let patch = patch.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()?;
+    Some((major, minor, patch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn parses_release_and_prerelease_versions() {
+        assert_eq!(parse_node_version("v20.11.1"), Some((20, 11, 1)));
+        assert_eq!(parse_node_version("20.11.1"), Some((20, 11, 1)));
+        assert_eq!(parse_node_version("v21.0.0-rc.1"), Some((21, 0, 0)));
+        assert_eq!(parse_node_version("v22.0.0-nightly20240101abcdef"), Some((22, 0, 0)));
+    }
+    
+    #[test]
+    fn rejects_unparseable_versions() {
+        for raw in ["", "v", "node", "v18", "v18.17", "vX.Y.Z", "v18.x.0", "v18.17.-1"] {
+            assert_eq!(parse_node_version(raw), None, "{:?}", raw);
+        }
+    }
+    
+    #[test]
+    fn minimum_compares_numerically() {
+        let minimum = (18, 17, 0);
+        assert!(meets_minimum("v18.17.0", (18, 17, 0), minimum));
+        assert!(meets_minimum("v18.19.1", (18, 19, 1), minimum));
+        assert!(meets_minimum("v20.0.0", (20, 0, 0), minimum));
+        assert!(!meets_minimum("v18.16.9", (18, 16, 9), minimum));
+        assert!(!meets_minimum("v9.99.99", (9, 99, 99), minimum));
+    }
+    
+    #[test]
+    fn prerelease_of_minimum_is_too_old() {
+        let minimum = (18, 17, 0);
+        assert!(!meets_minimum("v18.17.0-rc.1", (18, 17, 0), minimum));
+        assert!(meets_minimum("v18.17.1-rc.1", (18, 17, 1), minimum));
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn shell_path_skips_rc_file_noise() {
+        let output = format!("Welcome back!\n{}/opt/homebrew/bin:/usr/bin::/bin\nbye\n", SHELL_PATH_MARKER);
+        
+        assert_eq!(
+            parse_shell_path(&output),
+            [PathBuf::from("/opt/homebrew/bin"), PathBuf::from("/usr/bin"), PathBuf::from("/bin")]
+        );
+        assert!(parse_shell_path("no marker here\n").is_empty());
+    }
+    
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    fn touch(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    #[test]
+    fn version_managers_sorted_newest_first() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path();
+        let nvm = home.join(".nvm/versions/node");
+        touch(&nvm.join("v9.11.2/bin/node"));
+        touch(&nvm.join("v18.17.0/bin/node"));
+        touch(&nvm.join("v20.11.1/bin/node"));
+        touch(&home.join(".local/share/fnm/node-versions/v19.9.0/installation/bin/node"));
+        touch(&home.join(".asdf/installs/nodejs/21.6.2/bin/node"));
+        touch(&home.join(".volta/bin/node"));
+        touch(&home.join(".asdf/shims/node"));
+        
+        assert_eq!(
+            version_manager_nodes(home),
+            vec![
+                home.join(".asdf/installs/nodejs/21.6.2/bin/node"),
+                nvm.join("v20.11.1/bin/node"),
+                home.join(".local/share/fnm/node-versions/v19.9.0/installation/bin/node"),
+                nvm.join("v18.17.0/bin/node"),
+                nvm.join("v9.11.2/bin/node"),
+                home.join(".volta/bin/node"),
+                home.join(".asdf/shims/node"),
+            ]
+        );
+    }
+    
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    #[test]
+    fn version_managers_skip_incomplete_and_foreign_dirs() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path();
+        let nvm = home.join(".nvm/versions/node");
+        // An interrupted install leaves the version dir without a binary
+        std::fs::create_dir_all(nvm.join("v22.0.0/bin")).unwrap();
+        touch(&nvm.join("system/bin/node"));
+        touch(&nvm.join("v20.11.1/bin/node"));
+        
+        assert_eq!(version_manager_nodes(home), vec![nvm.join("v20.11.1/bin/node")]);
+    }
+    
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    #[test]
+    fn version_managers_empty_home() {
+        let home = tempfile::tempdir().unwrap();
+        
+        assert!(version_manager_nodes(home.path()).is_empty());
+    }
+}
@@ -0,0 +1,227 @@
+use std::time::{Duration, Instant};
+
+#[cfg(all(unix, not(target_os = "linux")))]
+use crate::command::command;
+
+/// Memory and CPU use of the server process, for `get_server_stats` and
+/// the `server-stats` events.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub(crate) struct ProcessStats {
+    pub(crate) pid: u32,
+    /// Resident set size (the working set on Windows)
+    pub(crate) rss_bytes: u64,
+    /// Virtual size (the commit charge on Windows)
+    pub(crate) virtual_bytes: Option<u64>,
+    /// Share of one core since the previous sample, so it can exceed 100
+    /// on several cores. `None` for the first sample of a process.
+    pub(crate) cpu_percent: Option<f64>,
+}
+
+/// What the OS reports about a process at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Usage {
+    rss_bytes: u64,
+    virtual_bytes: Option<u64>,
+    /// User plus system time used since the process started
+    cpu_time: Duration,
+}
+
+/// Turns the cumulative CPU time the OS reports into a percentage by
+/// comparing with the previous sample of the same process.
+#[derive(Debug, Default)]
+pub(crate) struct StatsSampler {
+    last: Option<(u32, Instant, Duration)>,
+}
+
+impl StatsSampler {
+    /// `None` if `pid` is gone or can't be queried. The process may exit
+    /// between the caller reading the PID and this asking for it, which
+    /// only ever shows up as `None`.
+    pub(crate) fn sample(&mut self, pid: u32) -> Option<ProcessStats> {
+        let usage = process_usage(pid)?;
+        Some(self.record(pid, usage, Instant::now()))
+    }
+    
+    fn record(&mut self, pid: u32, usage: Usage, at: Instant) -> ProcessStats {
+        let cpu_percent = match self.last {
+            Some((last_pid, last_at, last_cpu)) if last_pid == pid && at > last_at => {
+                let cpu = usage.cpu_time.saturating_sub(last_cpu);
+                Some(cpu.as_secs_f64() / (at - last_at).as_secs_f64() * 100.0)
+            }
+            _ => None,
+        };
+        self.last = Some((pid, at, usage.cpu_time));
+        ProcessStats { pid, rss_bytes: usage.rss_bytes, virtual_bytes: usage.virtual_bytes, cpu_percent }
+    }
+}
+
+/// Reads `/proc`, which costs no more than a couple of small file reads.
+#[cfg(target_os = "linux")]
+fn process_usage(pid: u32) -> Option<Usage> {
+    let statm = std::fs::read_to_string(format!("/proc/{}/statm", pid)).ok()?;
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // SAFETY: sysconf has no preconditions
+    let (page_size, ticks) = unsafe { (libc::sysconf(libc::_SC_PAGESIZE), libc::sysconf(libc::_SC_CLK_TCK)) };
+    if page_size <= 0 || ticks <= 0 {
+        return None;
+    }
+    let (virtual_pages, resident_pages) = parse_statm(&statm)?;
+    let cpu_ticks = parse_stat_cpu_ticks(&stat)?;
+    Some(Usage {
+        rss_bytes: resident_pages * page_size as u64,
+        virtual_bytes: Some(virtual_pages * page_size as u64),
+        cpu_time: Duration::from_secs_f64(cpu_ticks as f64 / ticks as f64),
+    })
+}
+
+/// Total and resident size in pages, the first two fields of `statm`.
+#[cfg(any(target_os = "linux", test))]
+fn parse_statm(statm: &str) -> Option<(u64, u64)> {
+    let mut fields = statm.split_whitespace().map(|field| field.parse::<u64>().ok());
+    Some((fields.next()??, fields.next()??))
+}
+
+/// `utime` plus `stime` in clock ticks, fields 14 and 15 of `stat`. The
+/// command name in field 2 may contain spaces and parentheses, so fields
+/// are counted from the last `)`.
+#[cfg(any(target_os = "linux", test))]
+fn parse_stat_cpu_ticks(stat: &str) -> Option<u64> {
+    let (_, after_comm) = stat.rsplit_once(')')?;
+    // Field 3 (state) is the first one after the name
+    let mut fields = after_comm.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// macOS and the BSDs: one `ps` call.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_usage(pid: u32) -> Option<Usage> {
+    let output = command("ps")
+        .args(["-o", "rss=,vsz=,time=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_ps_usage(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// `rss vsz time` as printed by `ps -o rss=,vsz=,time=`, sizes in KiB.
+#[cfg(any(all(unix, not(target_os = "linux")), test))]
+fn parse_ps_usage(output: &str) -> Option<Usage> {
+    let mut fields = output.split_whitespace();
+    let rss_kib: u64 = fields.next()?.parse().ok()?;
+    let vsz_kib: u64 = fields.next()?.parse().ok()?;
+    Some(Usage {
+        rss_bytes: rss_kib * 1024,
+        virtual_bytes: Some(vsz_kib * 1024),
+        cpu_time: parse_cpu_time(fields.next()?)?,
+    })
+}
+
+/// `[[dd-]hh:]mm:ss[.ff]`, the format of the `ps` time column.
+#[cfg(any(all(unix, not(target_os = "linux")), test))]
+fn parse_cpu_time(value: &str) -> Option<Duration> {
+    let (days, clock) = match value.split_once('-') {
+        Some((days, clock)) => (days.parse::<f64>().ok()?, clock),
+        None => (0.0, value),
+    };
+    let mut seconds = 0.0;
+    for part in clock.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Duration::try_from_secs_f64(days * 86_400.0 + seconds).ok()
+}
+
+#[cfg(windows)]
+fn process_usage(pid: u32) -> Option<Usage> {
+    use windows_sys::Win32::Foundation::{CloseHandle, FILETIME};
+    use windows_sys::Win32::System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+    use windows_sys::Win32::System::Threading::{GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+    
+    // SAFETY: the handle is checked before use and closed on every path, and
+    // the out-parameters are plain structs sized as the API expects
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return None;
+        }
+        let mut memory = PROCESS_MEMORY_COUNTERS {
+            cb: std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+            ..Default::default()
+        };
+        let has_memory = GetProcessMemoryInfo(process, &mut memory, memory.cb) != 0;
+        let (mut created, mut exited, mut kernel, mut user) =
+            (FILETIME::default(), FILETIME::default(), FILETIME::default(), FILETIME::default());
+        let has_times = GetProcessTimes(process, &mut created, &mut exited, &mut kernel, &mut user) != 0;
+        CloseHandle(process);
+        if !has_memory || !has_times {
+            return None;
+        }
+        // FILETIME counts 100ns intervals
+        let hundred_ns = |time: FILETIME| (u64::from(time.dwHighDateTime) << 32) | u64::from(time.dwLowDateTime);
+        Some(Usage {
+            rss_bytes: memory.WorkingSetSize as u64,
+            virtual_bytes: Some(memory.PagefileUsage as u64),
+            cpu_time: Duration::from_nanos((hundred_ns(kernel) + hundred_ns(user)) * 100),
+        })
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn process_usage(_pid: u32) -> Option<Usage> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn usage(cpu_ms: u64) -> Usage {
+        Usage { rss_bytes: 1024, virtual_bytes: None, cpu_time: Duration::from_millis(cpu_ms) }
+    }
+    
+    #[test]
+    fn cpu_percent_compares_with_previous_sample() {
+        let mut sampler = StatsSampler::default();
+        let start = Instant::now();
+        
+        assert_eq!(sampler.record(7, usage(1000), start).cpu_percent, None);
+        let stats = sampler.record(7, usage(1500), start + Duration::from_secs(1));
+        assert_eq!(stats.cpu_percent, Some(50.0));
+        // A new process starts over
+        assert_eq!(sampler.record(8, usage(100), start + Duration::from_secs(2)).cpu_percent, None);
+    }
+    
+    #[test]
+    fn proc_stat_cpu_ticks_survive_odd_command_names() {
+        let stat = "4321 (node (server) x) S 1 4321 4321 0 -1 4194560 1234 0 0 0 250 50 0 0 20 0 11 0";
+        assert_eq!(parse_stat_cpu_ticks(stat), Some(300));
+        assert_eq!(parse_stat_cpu_ticks("4321 (node) S 1"), None);
+        assert_eq!(parse_statm("262144 12800 3000 10 0 20000 0\n"), Some((262144, 12800)));
+    }
+    
+    #[test]
+    fn ps_output_gives_usage() {
+        assert_eq!(
+            parse_ps_usage("  51200 4194304   1:02.50\n"),
+            Some(Usage {
+                rss_bytes: 51200 * 1024,
+                virtual_bytes: Some(4194304 * 1024),
+                cpu_time: Duration::from_millis(62_500),
+            })
+        );
+        assert_eq!(parse_cpu_time("1-02:00:00"), Some(Duration::from_secs(93_600)));
+        assert_eq!(parse_ps_usage(""), None);
+    }
+    
+    #[test]
+    fn own_process_can_be_sampled() {
+        let stats = StatsSampler::default().sample(std::process::id());
+        
+        if cfg!(any(unix, windows)) {
+            assert!(stats.is_some_and(|stats| stats.rss_bytes > 0));
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme};
+
+use crate::command::command;
+
+/// Node preload that makes the standalone server listen over HTTPS. Copied
+/// into the server directory by the build scripts from `scripts/`.
+pub(crate) const HTTPS_PRELOAD: &str = "https-preload.cjs";
+
+const CERT_FILE: &str = "localhost.crt";
+const KEY_FILE: &str = "localhost.key";
+const CERT_VALID_DAYS: &str = "825";
+
+/// The certificate and key the server uses when HTTPS is on.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TlsFiles {
+    pub(crate) cert: PathBuf,
+    pub(crate) key: PathBuf,
+}
+
+/// Whether to serve over HTTPS, set with `OLLY_HTTPS=1`. Off by default: the
+/// webview only accepts the self-signed certificate once the user trusts it.
+pub(crate) fn https_requested() -> bool {
+    matches!(std::env::var("OLLY_HTTPS").as_deref(), Ok("1") | Ok("true"))
+}
+
+/// Returns the certificate in `dir`, generating a self-signed one for
+/// localhost with `openssl` on first use. Reused across runs so a cert the
+/// user has trusted keeps working.
+pub(crate) fn ensure_self_signed(dir: &Path) -> io::Result<TlsFiles> {
+    let files = TlsFiles { cert: dir.join(CERT_FILE), key: dir.join(KEY_FILE) };
+    if files.cert.is_file() && files.key.is_file() {
+        return Ok(files);
+    }
+    
+    std::fs::create_dir_all(dir)?;
+    log::info!("Generating a self-signed certificate in {:?}", dir);
+    let output = command("openssl")
+        .args(["req", "-x509", "-newkey", "rsa:2048", "-nodes", "-sha256"])
+        .args(["-days", CERT_VALID_DAYS, "-subj", "/CN=localhost"])
+        .args(["-addext", "subjectAltName=DNS:localhost,IP:127.0.0.1,IP:::1"])
+        .arg("-keyout")
+        .arg(&files.key)
+        .arg("-out")
+        .arg(&files.cert)
+        .output()?;
+    if !output.status.success() {
+        // Don't leave half a pair behind for the next run to pick up
+        let _ = std::fs::remove_file(&files.cert);
+        let _ = std::fs::remove_file(&files.key);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!("openssl failed ({}): {}", output.status, stderr.trim())));
+    }
+    Ok(files)
+}
+
+/// Sends `request` to `addr` over TLS and returns up to `limit` bytes of the
+/// answer. With `trusted` the handshake only succeeds if the server presents
+/// exactly that certificate, so the app's own self-signed one is accepted
+/// and nothing else; without it any certificate will do. `None` if the
+/// certificate can't be read, the handshake fails or nothing came back
+/// within `timeout`.
+pub(crate) fn https_exchange(
+    addr: &SocketAddr,
+    request: &str,
+    trusted: Option<&Path>,
+    timeout: Duration,
+    limit: u64,
+) -> Option<Vec<u8>> {
+    let pinned = match trusted {
+        Some(cert) => Some(CertificateDer::from_pem_file(cert).ok()?),
+        None => None,
+    };
+    let provider = Arc::new(ring::default_provider());
+    let verifier = PinnedCertificate { pinned, provider: provider.clone() };
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .ok()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    let mut connection = ClientConnection::new(Arc::new(config), ServerName::from(addr.ip())).ok()?;
+    
+    let mut stream = TcpStream::connect_timeout(addr, timeout).ok()?;
+    let _ = stream.set_read_timeout(Some(timeout));
+    let _ = stream.set_write_timeout(Some(timeout));
+    let mut tls = rustls::Stream::new(&mut connection, &mut stream);
+    // Drives the handshake, so an untrusted certificate fails here
+    tls.write_all(request.as_bytes()).ok()?;
+    
+    // A timeout, or Node closing without close_notify, keeps what came
+    let mut response = Vec::new();
+    let _ = tls.take(limit).read_to_end(&mut response);
+    (!response.is_empty()).then_some(response)
+}
+
+/// Accepts the server's certificate if it is the pinned one (or any, with
+/// nothing pinned), still checking that the server holds its key. The
+/// app's certificate is self-signed, so names and issuers prove nothing.
+#[derive(Debug)]
+struct PinnedCertificate {
+    pinned: Option<CertificateDer<'static>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match &self.pinned {
+            Some(pinned) if pinned != end_entity => {
+                Err(rustls::Error::InvalidCertificate(rustls::CertificateError::UnknownIssuer))
+            }
+            _ => Ok(ServerCertVerified::assertion()),
+        }
+    }
+    
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+    
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+    
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn existing_certificate_is_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(CERT_FILE), "cert").unwrap();
+        std::fs::write(dir.path().join(KEY_FILE), "key").unwrap();
+        
+        let files = ensure_self_signed(dir.path()).unwrap();
+        
+        assert_eq!(files.cert, dir.path().join(CERT_FILE));
+        assert_eq!(std::fs::read_to_string(&files.cert).unwrap(), "cert");
+        assert_eq!(std::fs::read_to_string(&files.key).unwrap(), "key");
+    }
+    
+    /// Writes a fresh self-signed localhost certificate to `dir`, the way
+    /// `ensure_self_signed` would but without needing openssl.
+    fn self_signed(dir: &Path) -> (TlsFiles, rcgen::CertifiedKey) {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let files = TlsFiles { cert: dir.join(CERT_FILE), key: dir.join(KEY_FILE) };
+        std::fs::write(&files.cert, certified.cert.pem()).unwrap();
+        std::fs::write(&files.key, certified.key_pair.serialize_pem()).unwrap();
+        (files, certified)
+    }
+    
+    /// Serves `connections` HTTPS requests with `certified` on a free port,
+    /// answering each with `ok`.
+    fn serve_https(certified: rcgen::CertifiedKey, connections: usize) -> SocketAddr {
+        let key = rustls::pki_types::PrivateKeyDer::Pkcs8(certified.key_pair.serialize_der().into());
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![certified.cert.der().clone()], key)
+            .unwrap();
+        let config = Arc::new(config);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(connections) {
+                let mut stream = stream.unwrap();
+                let mut connection = rustls::ServerConnection::new(config.clone()).unwrap();
+                let mut tls = rustls::Stream::new(&mut connection, &mut stream);
+                let mut request = [0; 1024];
+                // Fails when the client rejects the certificate
+                if tls.read(&mut request).is_ok() {
+                    let _ = tls.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+                    tls.conn.send_close_notify();
+                    let _ = tls.flush();
+                }
+            }
+        });
+        addr
+    }
+    
+    #[test]
+    fn https_exchange_only_trusts_the_given_certificate() {
+        let (dir, other) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (files, certified) = self_signed(dir.path());
+        let (other, _) = self_signed(other.path());
+        let addr = serve_https(certified, 3);
+        let request = "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+        let timeout = Duration::from_secs(5);
+        
+        let trusted = https_exchange(&addr, request, Some(&files.cert), timeout, 1024);
+        let untrusted = https_exchange(&addr, request, Some(&other.cert), timeout, 1024);
+        let unpinned = https_exchange(&addr, request, None, timeout, 1024);
+        
+        assert!(trusted.is_some_and(|response| response.starts_with(b"HTTP/1.1 200")));
+        assert_eq!(untrusted, None);
+        assert!(unpinned.is_some_and(|response| response.ends_with(b"ok")));
+    }
+}
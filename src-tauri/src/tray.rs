@@ -0,0 +1,145 @@
+use tauri::image::Image;
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Listener};
+
+use crate::{restart_server, show_main_window, OPEN_LOGS_MENU_ID};
+
+const TRAY_ID: &str = "main";
+const ICON_SIZE: u32 = 32;
+
+/// What the tray icon shows about the bundled server.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TrayStatus {
+    Starting,
+    Running,
+    /// Running but failing its health checks
+    Unresponsive,
+    Crashed,
+}
+
+impl TrayStatus {
+    fn color(self) -> [u8; 3] {
+        match self {
+            TrayStatus::Starting => [0xf5, 0xb7, 0x00],
+            TrayStatus::Running => [0x2e, 0xb8, 0x4b],
+            TrayStatus::Unresponsive | TrayStatus::Crashed => [0xe0, 0x3e, 0x2f],
+        }
+    }
+    
+    fn tooltip(self) -> &'static str {
+        match self {
+            TrayStatus::Starting => "Olly Molly: server starting",
+            TrayStatus::Running => "Olly Molly: server running",
+            TrayStatus::Unresponsive => "Olly Molly: server not responding",
+            TrayStatus::Crashed => "Olly Molly: server stopped",
+        }
+    }
+}
+
+/// Which server events move the icon to which status. The crash monitor's
+/// `server-exited` comes before it tries again with `server-starting`, and
+/// the health monitor's `server-unhealthy` before `server-recovered`.
+const STATUS_EVENTS: [(&str, TrayStatus); 9] = [
+    ("server-starting", TrayStatus::Starting),
+    ("server-restarting", TrayStatus::Starting),
+    ("server-ready", TrayStatus::Running),
+    ("server-recovered", TrayStatus::Running),
+    ("server-unhealthy", TrayStatus::Unresponsive),
+    ("server-exited", TrayStatus::Crashed),
+    ("server-crashed", TrayStatus::Crashed),
+    ("server-failed", TrayStatus::Crashed),
+    ("server-restart-failed", TrayStatus::Crashed),
+];
+
+/// Adds the tray icon with Show Window, Restart Server, Open Logs Folder and
+/// Quit, and keeps its colour in step with the server events.
+pub(crate) fn create_tray(app: &AppHandle) -> tauri::Result<()> {
+    let menu = Menu::with_items(
+        app,
+        &[
+            &MenuItem::with_id(app, "show", "Show Window", true, None::<&str>)?,
+            &MenuItem::with_id(app, "restart", "Restart Server", true, None::<&str>)?,
+            &MenuItem::with_id(app, OPEN_LOGS_MENU_ID, "Open Logs Folder", true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?,
+        ],
+    )?;
+    TrayIconBuilder::with_id(TRAY_ID)
+        .icon(status_icon(TrayStatus::Starting))
+        .tooltip(TrayStatus::Starting.tooltip())
+        .menu(&menu)
+        .show_menu_on_left_click(true)
+        .on_menu_event(handle_menu_event)
+        .build(app)?;
+    
+    for (event, status) in STATUS_EVENTS {
+        let handle = app.clone();
+        app.listen_any(event, move |_| set_tray_status(&handle, status));
+    }
+    Ok(())
+}
+
+fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        "show" => show_main_window(app),
+        "restart" => {
+            let handle = app.clone();
+            tauri::async_runtime::spawn(async move {
+                // Failures are logged and emitted by restart_server itself
+                let _ = restart_server(handle).await;
+            });
+        }
+        // Goes through RunEvent::Exit, which stops the servers gracefully
+        "quit" => app.exit(0),
+        // OPEN_LOGS_MENU_ID is handled by the app menu's handler, which
+        // gets tray menu events as well
+        _ => {}
+    }
+}
+
+fn set_tray_status(app: &AppHandle, status: TrayStatus) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else { return };
+    if let Err(e) = tray.set_icon(Some(status_icon(status))) {
+        log::warn!("Failed to update the tray icon: {}", e);
+    }
+    let _ = tray.set_tooltip(Some(status.tooltip()));
+}
+
+/// A filled dot in the colour of `status`, drawn here rather than shipped as
+/// an image file per status.
+fn status_icon(status: TrayStatus) -> Image<'static> {
+    let [r, g, b] = status.color();
+    let center = (ICON_SIZE as f32 - 1.0) / 2.0;
+    let radius = ICON_SIZE as f32 / 2.0 - 2.0;
+    let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let distance = (x as f32 - center).hypot(y as f32 - center);
+            // One pixel of falloff keeps the edge from looking jagged
+            let alpha = (radius + 0.5 - distance).clamp(0.0, 1.0);
+            rgba.extend_from_slice(&[r, g, b, (alpha * 255.0) as u8]);
+        }
+    }
+    Image::new_owned(rgba, ICON_SIZE, ICON_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn pixel(icon: &Image, x: u32, y: u32) -> [u8; 4] {
+        let offset = ((y * icon.width() + x) * 4) as usize;
+        icon.rgba()[offset..offset + 4].try_into().unwrap()
+    }
+    
+    #[test]
+    fn status_icon_is_a_coloured_dot() {
+        let icon = status_icon(TrayStatus::Crashed);
+        
+        assert_eq!((icon.width(), icon.height()), (ICON_SIZE, ICON_SIZE));
+        assert_eq!(pixel(&icon, ICON_SIZE / 2, ICON_SIZE / 2), [0xe0, 0x3e, 0x2f, 255]);
+        assert_eq!(pixel(&icon, 0, 0)[3], 0);
+        assert_ne!(status_icon(TrayStatus::Running).rgba(), icon.rgba());
+    }
+}